@@ -0,0 +1,63 @@
+//! Contact sheets, showing the same image mapped with different algorithms side by side
+//!
+//! This allows picking the algorithm which works best for a specific image and palette.
+use image::{DynamicImage, Rgba, RgbaImage, imageops};
+
+use crate::font;
+
+/// Space between cells and around the edges of the sheet
+const GAP: u32 = 8;
+/// Scale of the labels' font
+const LABEL_SCALE: u32 = 2;
+/// Background color of the sheet
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// Color of the labels
+const LABEL_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Arrange `cells` in a grid, with every image labeled by its name underneath
+///
+/// The grid is as square as possible, filled row by row. All cells are as large as the largest
+/// image or label.
+pub fn render(cells: &[(String, DynamicImage)]) -> RgbaImage {
+    let count = u32::try_from(cells.len()).unwrap_or(u32::MAX);
+
+    let columns = count.isqrt() + u32::from(count.isqrt().pow(2) < count);
+    let rows = count.div_ceil(columns.max(1));
+
+    let cell_width = cells
+        .iter()
+        .map(|(label, img)| img.width().max(font::text_width(label, LABEL_SCALE)))
+        .max()
+        .unwrap_or_default();
+    let cell_height = cells
+        .iter()
+        .map(|(_, img)| img.height())
+        .max()
+        .unwrap_or_default();
+
+    let label_height = font::GLYPH_HEIGHT * LABEL_SCALE + GAP;
+
+    let mut sheet = RgbaImage::from_pixel(
+        columns * (cell_width + GAP) + GAP,
+        rows * (cell_height + label_height + GAP) + GAP,
+        BACKGROUND,
+    );
+
+    for (i, (label, img)) in (0..).zip(cells) {
+        let x = GAP + (i % columns) * (cell_width + GAP);
+        let y = GAP + (i / columns) * (cell_height + label_height + GAP);
+
+        imageops::overlay(&mut sheet, &img.to_rgba8(), x.into(), y.into());
+
+        font::draw_text(
+            &mut sheet,
+            x,
+            y + cell_height + GAP / 2,
+            label,
+            LABEL_SCALE,
+            LABEL_COLOR,
+        );
+    }
+
+    sheet
+}
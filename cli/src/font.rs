@@ -0,0 +1,111 @@
+//! A tiny bitmap font for labeling generated images
+//!
+//! Every glyph is 3 x 5 pixels. Only uppercase letters, digits and a few symbols are included,
+//! lowercase letters are drawn as uppercase and any other character as `?`.
+use image::{Rgba, RgbaImage};
+
+/// Width of a single glyph in pixels, before scaling
+pub const GLYPH_WIDTH: u32 = 3;
+/// Height of a single glyph in pixels, before scaling
+pub const GLYPH_HEIGHT: u32 = 5;
+
+/// The rows of the glyph for `c`, with the leftmost pixel in the third bit
+const fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ' ' => [0; 5],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Width in pixels of `text` drawn with [`draw_text`]
+///
+/// Glyphs are separated by a single (scaled) pixel.
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    let chars = u32::try_from(text.chars().count()).unwrap_or(u32::MAX);
+
+    (chars * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// Draw `text` with its top left corner at `x`, `y`
+///
+/// Every pixel of a glyph becomes a `scale` x `scale` square. Pixels outside of `img` are
+/// skipped.
+pub fn draw_text(img: &mut RgbaImage, x: u32, y: u32, text: &str, scale: u32, color: Rgba<u8>) {
+    for (i, c) in (0..).zip(text.chars()) {
+        let left = x + i * (GLYPH_WIDTH + 1) * scale;
+
+        for (row, bits) in (0..).zip(glyph(c)) {
+            for col in (0..GLYPH_WIDTH).filter(|col| bits & (0b100 >> col) != 0) {
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    let (px, py) = (left + col * scale + dx, y + row * scale + dy);
+
+                    if px < img.width() && py < img.height() {
+                        img.put_pixel(px, py, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GLYPH_HEIGHT, draw_text, text_width};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn drawn_text_fits_width() {
+        let text = "CIE76 #ff00AA";
+        let scale = 2;
+
+        let mut img = RgbaImage::new(text_width(text, scale) + 4, GLYPH_HEIGHT * scale);
+        draw_text(&mut img, 0, 0, text, scale, Rgba([255; 4]));
+
+        let drawn = |x: u32| (0..img.height()).any(|y| img.get_pixel(x, y)[3] != 0);
+
+        // the first and last column of the text are both used
+        assert!(drawn(0));
+        assert!(drawn(text_width(text, scale) - 1));
+        assert!(!(text_width(text, scale)..img.width()).any(drawn));
+    }
+}
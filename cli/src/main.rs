@@ -5,6 +5,8 @@
 //! The used palette is read from a file. Currently supported formats for this file are:
 //!
 //! - json
+//! - swatches (Procreate)
+//! - line-wise (no extension): hex colors separated by whitespace, eg.: `#ffeecc`
 //!
 //! ## Usage
 //!
@@ -14,72 +16,188 @@
 use anyhow::{Ok, Result, anyhow, bail};
 use clap::{
     CommandFactory, Parser,
-    builder::{PossibleValuesParser, TypedValueParser},
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
 };
 use clap_complete::CompleteEnv;
-use image::DynamicImage;
+use image::{DynamicImage, GrayImage, ImageFormat};
 use palette_mapper_palettes::{Base16, Base24};
 use std::{
     fs::File,
-    io::BufReader,
+    io::{BufReader, BufWriter},
     path::{Path, PathBuf},
+    thread,
+    time::Duration,
 };
+use strum::IntoEnumIterator;
 
+mod contact_sheet;
+mod font;
 mod step;
+mod swatch_card;
+mod template;
 
-use palette_mapper::{Palette, distance::Algorithms, map_image_to_palette};
+use palette_mapper::{
+    MapOptions, Palette,
+    distance::Algorithms,
+    indexed::IndexedImage,
+    map_image_to_palette_with,
+    mask::map_image_to_palette_masked,
+    options::CancellationToken,
+    palette::format::{FormatRegistry, LineWise},
+};
 
 use step::StepBuilder;
 
+/// Maximum width and height of the cells of `--compare-algorithms`
+const COMPARE_MAX_SIZE: u32 = 256;
+
 /// CLI struct containing options passed by user
 #[derive(Parser)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "The bools are independent command line flags."
+)]
 #[clap(about = "Convert an image to a color palette")]
 struct Cli {
     /// Path to input image
-    input: PathBuf,
+    #[arg(
+        required_unless_present_any = ["emit_rust", "palette_schema", "list_themes", "swatch_card"],
+        requires = "palette_source"
+    )]
+    input: Option<PathBuf>,
+    /// Decode the input image as this format instead of guessing it from its contents
+    #[arg(long,
+        value_parser = PossibleValuesParser::new(readable_formats()).map(|s| ImageFormat::from_extension(s).unwrap())
+    )]
+    input_format: Option<ImageFormat>,
     /// Distance Algorithm used to determine distance between colors
     #[arg(long, short, value_enum,
         value_parser = PossibleValuesParser::new(<Algorithms as strum::VariantNames>::VARIANTS).map(|s| s.parse::<Algorithms>().unwrap()),
         default_value = Algorithms::EuclideanDistance.to_string())]
     algorithm: Algorithms,
     /// Path to file containing palette
-    #[arg(short, long, group = "palette_source", required = true)]
+    #[arg(short, long, group = "palette_source")]
     palette: Option<PathBuf>,
     #[arg(long, value_enum,
         value_parser = PossibleValuesParser::new(<Base16 as strum::VariantNames>::VARIANTS).map(|s| s.parse::<Base16>().unwrap()),
-        group = "palette_source",
-        required = true
+        groups = ["palette_source", "theme"]
     )]
     /// Use a base16 theme
     base16: Option<Base16>,
     #[arg(long, value_enum,
         value_parser = PossibleValuesParser::new(<Base24 as strum::VariantNames>::VARIANTS).map(|s| s.parse::<Base24>().unwrap()),
-        group = "palette_source",
-        required = true
+        groups = ["palette_source", "theme"]
     )]
     /// Use a base24 theme
     base24: Option<Base24>,
+    /// Only use these color slots of the base16 or base24 theme, e.g. "8,9,10,11"
+    ///
+    /// Slots are the indices of the theme's colors, starting at 0, so base16 themes have the
+    /// slots 0 to 15 and base24 themes 0 to 23.
+    #[arg(long, value_delimiter = ',', requires = "theme")]
+    slots: Vec<usize>,
     /// Output path
     ///
-    /// Having the path end with ".{ext}" will replace the extension with that of the input file.
+    /// The path may contain the tokens {stem}, {ext} and {parent} of the input file, as well as
+    /// {algorithm} and {palette}, e.g. "out/{palette}/{stem}.{ext}". Missing directories are
+    /// created.
+    ///
+    /// Ending the path with ".svg" writes every pixel as a rectangle. This is only sensible for
+    /// small images, such as pixel art.
+    ///
+    /// Ending the path with ".ora" writes the mapped image on top of the original one as separate
+    /// layers, which can be edited in e.g. GIMP or Krita.
     #[arg(long, short, default_value = "output.{ext}")]
     output: PathBuf,
+    /// Refuse to replace an existing output file (default)
+    #[arg(long, overrides_with = "overwrite")]
+    no_clobber: bool,
+    /// Replace the output file if it already exists
+    #[arg(long, overrides_with = "no_clobber")]
+    overwrite: bool,
     /// If an interactive output of the individual steps should be printed
     ///
     /// Disabling this can be useful in scripting context where pretty output is not needed.
     #[arg(long)]
     non_interactive: bool,
+    /// Print the palette as a `color_palette!` invocation instead of mapping an image
+    ///
+    /// The output can be pasted directly into Rust source using the `palette_mapper` library.
+    #[arg(long, requires = "palette_source")]
+    emit_rust: bool,
+    /// Save the palette as a swatch card image to this path instead of mapping an image
+    #[arg(long, requires = "palette_source", conflicts_with_all = ["input", "emit_rust"])]
+    swatch_card: Option<PathBuf>,
+    /// Write the hex value of every color beneath its swatch on the swatch card
+    #[arg(long, requires = "swatch_card")]
+    legend: bool,
+    /// Print the JSON Schema of palette json files
+    ///
+    /// This can be used by editors to validate and autocomplete palette files.
+    #[arg(long, exclusive = true)]
+    palette_schema: bool,
+    /// List the available base16 and base24 themes
+    #[arg(long, conflicts_with_all = ["input", "palette_source", "emit_rust"])]
+    list_themes: bool,
+    /// Only list dark themes
+    #[arg(long, requires = "list_themes", conflicts_with = "light")]
+    dark: bool,
+    /// Only list light themes
+    #[arg(long, requires = "list_themes")]
+    light: bool,
+    /// Only list the variants of one theme family, e.g. "gruvbox"
+    ///
+    /// The family is the theme's name without variant words like "dark", "light" or "hard".
+    #[arg(long, requires = "list_themes")]
+    theme_family: Option<String>,
+    /// Map every pixel to the palette color closest in brightness, so darker pixels never end
+    /// up brighter than lighter ones
+    ///
+    /// Best suited for grayscale or tonal images and palettes. Ignores --algorithm.
+    #[arg(long)]
+    monotone: bool,
+    /// Snap the alpha value of each pixel to the closest alpha value found in the palette,
+    /// independently of its color
+    #[arg(long)]
+    quantize_alpha: bool,
+    /// Abort mapping the image if it takes longer than this many seconds
+    #[arg(long)]
+    timeout: Option<f32>,
+    /// Grayscale image controlling how strongly each pixel is mapped
+    ///
+    /// White pixels are fully mapped, black pixels keep their original color and grays blend the
+    /// two. The mask is resized to match the input image.
+    #[arg(long)]
+    mask: Option<PathBuf>,
+    /// Map the image with every algorithm and save a labeled grid of the results
+    ///
+    /// Large images are scaled down first. The {algorithm} token of the output path expands to
+    /// "all". The grid can't be written as ".ora" file, since it has no original to layer it on.
+    #[arg(long, conflicts_with = "algorithm")]
+    compare_algorithms: bool,
 }
 
 impl Cli {
     /// Get the palette passed by the user independent of how it was passed
     fn get_palette(&self) -> Result<Palette> {
-        if let Some(pal) = self.base16.map(std::convert::Into::into) {
-            return Ok(pal);
+        if let Some(theme) = self.base16 {
+            self.check_slots(theme, Palette::from(theme).len())?;
+
+            return Ok(if self.slots.is_empty() {
+                theme.into()
+            } else {
+                theme.palette_slots(&self.slots)
+            });
         }
 
-        if let Some(pal) = self.base24.map(std::convert::Into::into) {
-            return Ok(pal);
+        if let Some(theme) = self.base24 {
+            self.check_slots(theme, Palette::from(theme).len())?;
+
+            return Ok(if self.slots.is_empty() {
+                theme.into()
+            } else {
+                theme.palette_slots(&self.slots)
+            });
         }
 
         if let Some(ref pal) = self.palette {
@@ -88,6 +206,78 @@ impl Cli {
 
         unreachable!("A source for the palette should always be given.")
     }
+
+    /// Check the image can be saved to `path`
+    fn check_output(&self, path: &Path, is_ora: bool) -> Result<()> {
+        if self.compare_algorithms && is_ora {
+            bail!("--compare-algorithms can't be written as .ora file");
+        }
+
+        if !self.overwrite && path.exists() {
+            bail!(
+                "output file {} already exists (use --overwrite to replace it)",
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check all [slots](Cli::slots) are among the `colors` colors of `theme`
+    fn check_slots(&self, theme: impl std::fmt::Display, colors: usize) -> Result<()> {
+        if let Some(slot) = self.slots.iter().find(|&&slot| slot >= colors) {
+            bail!(
+                "slot {slot} is out of range, {theme} only has the slots 0 to {}",
+                colors - 1
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Get the options for mapping the image to `palette`
+    ///
+    /// If a timeout was given, this starts the timer.
+    fn map_options(&self, palette: &Palette) -> Result<MapOptions> {
+        // Opaque palettes (such as all base16/24 themes) would otherwise make transparent images
+        // opaque
+        let mut options = MapOptions::default()
+            .ignore_alpha(palette.is_opaque())
+            .monotone(self.monotone)
+            .quantize_alpha(self.quantize_alpha);
+
+        if let Some(timeout) = self.timeout {
+            let token = CancellationToken::new();
+            options = options.cancellation_token(token.clone());
+
+            let timeout = Duration::try_from_secs_f32(timeout)?;
+            thread::spawn(move || {
+                thread::sleep(timeout);
+                token.cancel();
+            });
+        }
+
+        Ok(options)
+    }
+
+    /// Get the name of the palette, used for the `{palette}` token of the output path
+    fn palette_name(&self) -> String {
+        if let Some(pal) = self.base16 {
+            return pal.to_string();
+        }
+
+        if let Some(pal) = self.base24 {
+            return pal.to_string();
+        }
+
+        self.palette
+            .as_ref()
+            .and_then(|pal| pal.file_stem())
+            .map_or_else(
+                || "palette".to_string(),
+                |s| s.to_string_lossy().to_string(),
+            )
+    }
 }
 
 fn main() -> Result<()> {
@@ -95,10 +285,68 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    if cli.palette_schema {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&palette_mapper::palette::schema::schema())?
+        );
+
+        return Ok(());
+    }
+
+    if cli.list_themes {
+        print!(
+            "{}",
+            list_themes(cli.dark, cli.light, cli.theme_family.as_deref())
+        );
+
+        return Ok(());
+    }
+
+    if let Some(ref path) = cli.swatch_card {
+        save_plain_image(
+            &DynamicImage::from(swatch_card::render(&cli.get_palette()?, cli.legend)),
+            path,
+        )?;
+
+        return Ok(());
+    }
+
+    if cli.emit_rust {
+        println!("{}", palette_to_rust(&cli.get_palette()?));
+
+        return Ok(());
+    }
+
+    let input = cli
+        .input
+        .as_ref()
+        .expect("clap ensures an input is given unless emitting rust");
+
     if cli.non_interactive {
         let _ = step::INTERACTIVE.set(false);
     }
 
+    let output_path = template::expand(
+        &cli.output,
+        &template::TemplateValues {
+            input,
+            algorithm: &if cli.compare_algorithms {
+                "all".to_string()
+            } else {
+                cli.algorithm.to_string()
+            },
+            palette: &cli.palette_name(),
+        },
+    )?;
+
+    let is_ora = output_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ora"));
+
+    // Checked before doing any work, so no time is wasted on an image which can't be saved
+    cli.check_output(&output_path, is_ora)?;
+
     let mut steps = StepBuilder::new(vec![
         "Loading palette".to_string(),
         "Loading image".to_string(),
@@ -110,25 +358,43 @@ fn main() -> Result<()> {
     let palette = cli.get_palette()?;
 
     steps.next().unwrap();
-    let mut img = open_image(&cli.input)?;
+    let mut img = open_image(input, cli.input_format)?;
+    let mask = cli.mask.as_deref().map(open_mask).transpose()?;
+    let original = is_ora.then(|| img.clone());
 
     steps.next().unwrap();
-    map_image_to_palette(&mut img, &palette, &cli.algorithm);
+    let options = cli.map_options(&palette)?;
+
+    let map = |img: &mut DynamicImage, algorithm: &Algorithms| {
+        match mask {
+            Some(ref mask) => map_image_to_palette_masked(img, &palette, algorithm, mask, &options),
+            None => map_image_to_palette_with(img, &palette, algorithm, &options),
+        }
+        .map_err(|e| match e {
+            palette_mapper::Error::Cancelled => anyhow!("mapping the image exceeded the timeout"),
+            e => e.into(),
+        })
+    };
 
-    let mut output_path = cli.output;
+    if cli.compare_algorithms {
+        img = compare_algorithms(&img, map)?;
+    } else {
+        map(&mut img, &cli.algorithm)?;
+    }
 
     steps.next().unwrap();
-    #[allow(clippy::literal_string_with_formatting_args, reason = "False positive")]
-    if output_path.extension().is_some_and(|ext| ext == "{ext}") {
-        if let Some(input_ext) = cli.input.extension() {
-            output_path.set_extension(input_ext);
-        } else {
-            output_path.set_extension("");
-        }
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
     }
 
-    img.save(output_path)
-        .map_err(|_| anyhow!("unsupported output format"))?;
+    if let Some(original) = original {
+        palette_mapper::ora::write_ora(
+            &[("Mapped", &img), ("Original", &original)],
+            BufWriter::new(File::create(&output_path)?),
+        )?;
+    } else {
+        save_image(&img, &palette, &output_path)?;
+    }
 
     // We are at the end of the cli, there should be no more steps left
     assert!(steps.next().is_none());
@@ -136,40 +402,230 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-/// Attempt to read the provided path and deserialize the contents to a [`Palette`]
+/// Map `img` with every algorithm using `map`, returning a labeled grid of the results
 ///
-/// Currently only supports json.
-fn read_palette(palette: &PathBuf) -> Result<Palette> {
-    let format = palette.extension().map_or_else(
-        || {
-            eprintln!("No extension on palette path. Assuming line-wise.");
+/// Large images are scaled down first.
+fn compare_algorithms(
+    img: &DynamicImage,
+    map: impl Fn(&mut DynamicImage, &Algorithms) -> Result<()>,
+) -> Result<DynamicImage> {
+    let img = if img.width() > COMPARE_MAX_SIZE || img.height() > COMPARE_MAX_SIZE {
+        img.thumbnail(COMPARE_MAX_SIZE, COMPARE_MAX_SIZE)
+    } else {
+        img.clone()
+    };
+
+    let cells = Algorithms::iter()
+        .map(|algorithm| {
+            let mut cell = img.clone();
+            map(&mut cell, &algorithm)?;
+
+            Ok((algorithm.to_string(), cell))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DynamicImage::from(contact_sheet::render(&cells)))
+}
+
+/// Save the mapped image to `path`, choosing the format by its extension
+///
+/// PNG and GIF files are written as indexed images using the palette where possible, which is
+/// smaller and exact.
+fn save_image(img: &DynamicImage, palette: &Palette, path: &Path) -> Result<()> {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
 
-            "line-wise".to_string()
+    match ext.as_deref() {
+        Some("svg") => std::fs::write(path, palette_mapper::svg::to_svg(img))?,
+        Some(ext @ ("png" | "gif")) => match IndexedImage::from_mapped(img, palette) {
+            Some(indexed) if ext == "png" => {
+                indexed.write_png(BufWriter::new(File::create(path)?))?;
+            }
+            Some(indexed) => indexed.write_gif(BufWriter::new(File::create(path)?))?,
+            None => save_plain_image(img, path)?,
         },
-        |v| v.to_string_lossy().to_string(),
-    );
+        _ => save_plain_image(img, path)?,
+    }
 
-    match format.as_str() {
-        "json" => {
-            let file = File::open(palette)?;
+    Ok(())
+}
 
-            let buffered = BufReader::new(file);
+/// Save `img` to `path`, choosing the format by its extension
+///
+/// PNG files are written with fixed encoder settings, so the output is reproducible.
+fn save_plain_image(img: &DynamicImage, path: &Path) -> Result<()> {
+    let is_png = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
 
-            Ok(serde_json::from_reader(buffered)?)
-        }
-        _ => bail!("Unsupported format for palette. Supported formats are: json"),
+    if is_png {
+        palette_mapper::encoding::write_png(img, BufWriter::new(File::create(path)?))?;
+    } else {
+        img.save(path)
+            .map_err(|_| anyhow!("unsupported output format"))?;
     }
+
+    Ok(())
+}
+
+/// Format the palette as a `color_palette!` macro invocation
+///
+/// The alpha value is omitted for fully opaque colors, matching the macro's default.
+fn palette_to_rust(palette: &Palette) -> String {
+    let colors: String = palette
+        .iter()
+        .map(|color| match color.0 {
+            [r, g, b, 255] => format!("    [{r}, {g}, {b}],\n"),
+            [r, g, b, alpha] => format!("    [{r}, {g}, {b}, {alpha}],\n"),
+        })
+        .collect();
+
+    format!("color_palette!(\n{colors})")
 }
 
-/// Opens the input image at the given path
-fn open_image<P>(path: P) -> Result<DynamicImage>
+/// List the names of all base16 and base24 themes, optionally only the dark or light ones, or
+/// only those in `family`
+///
+/// The names are sorted alphabetically and can be passed to `--base16` and `--base24`.
+fn list_themes(dark: bool, light: bool, family: Option<&str>) -> String {
+    /// Format the themes as an indented list under a heading
+    fn section<T: std::fmt::Display>(heading: &str, themes: impl Iterator<Item = T>) -> String {
+        let mut names: Vec<_> = themes.map(|t| t.to_string()).collect();
+        names.sort_unstable();
+
+        let names: String = names
+            .iter()
+            .flat_map(|t| ["  ", t.as_str(), "\n"])
+            .collect();
+
+        format!("{heading}:\n{names}")
+    }
+
+    let (mut base16, mut base24): (Vec<_>, Vec<_>) = if dark {
+        (
+            Base16::dark_themes().collect(),
+            Base24::dark_themes().collect(),
+        )
+    } else if light {
+        (
+            Base16::light_themes().collect(),
+            Base24::light_themes().collect(),
+        )
+    } else {
+        (Base16::iter().collect(), Base24::iter().collect())
+    };
+
+    if let Some(family) = family {
+        base16.retain(|t| t.family() == family);
+        base24.retain(|t| t.family() == family);
+    }
+
+    section("base16", base16.into_iter()) + &section("base24", base24.into_iter())
+}
+
+/// Attempt to read the provided path and deserialize the contents to a [`Palette`]
+///
+/// The format is chosen by the extension, see [`FormatRegistry`]. Paths without an extension are
+/// read as line-wise hex colors.
+fn read_palette(palette: &PathBuf) -> Result<Palette> {
+    let registry = FormatRegistry::default();
+
+    let format = if let Some(ext) = palette.extension() {
+        registry.get(&ext.to_string_lossy()).ok_or_else(|| {
+            anyhow!(
+                "Unsupported format for palette. Supported formats are: {}, line-wise",
+                registry.extensions().join(", ")
+            )
+        })?
+    } else {
+        eprintln!("No extension on palette path. Assuming line-wise.");
+
+        &LineWise
+    };
+
+    Ok(format.read(&mut BufReader::new(File::open(palette)?))?)
+}
+
+/// Opens the input image at the given path, guessing its format unless `format` is given
+fn open_image<P>(path: P, format: Option<ImageFormat>) -> Result<DynamicImage>
 where
     P: AsRef<Path>,
 {
-    Ok(image::ImageReader::open(path)
-        .map_err(|_| anyhow!("could not open input image"))?
-        .with_guessed_format()
-        .map_err(|_| anyhow!("could not determine input image format"))?
+    let reader =
+        image::ImageReader::open(path).map_err(|_| anyhow!("could not open input image"))?;
+
+    let reader = match format {
+        Some(format) => image::ImageReader::with_format(reader.into_inner(), format),
+        None => reader
+            .with_guessed_format()
+            .map_err(|_| anyhow!("could not determine input image format"))?,
+    };
+
+    reader
         .decode()
-        .expect("Format must have been determined at this point!"))
+        .map_err(|e| anyhow!("could not decode input image: {e}"))
+}
+
+/// All formats which can be decoded, as accepted by `--input-format`
+///
+/// Each format is named by its most common extension, with the other extensions as aliases.
+fn readable_formats() -> Vec<PossibleValue> {
+    ImageFormat::all()
+        .filter(ImageFormat::reading_enabled)
+        .filter_map(|format| {
+            let (name, aliases) = format.extensions_str().split_first()?;
+
+            Some(PossibleValue::new(*name).aliases(aliases.iter().copied()))
+        })
+        .collect()
+}
+
+/// Opens the mask image at the given path as grayscale
+fn open_mask(path: &Path) -> Result<GrayImage> {
+    image::open(path)
+        .map(|mask| mask.to_luma8())
+        .map_err(|e| anyhow!("could not read mask image {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{list_themes, palette_to_rust};
+    use palette_mapper::color_palette;
+
+    #[test]
+    fn emit_rust() {
+        let p = color_palette!([12, 45, 67, 200], [87, 212, 45], [0, 0, 0, 0]);
+        let rust = palette_to_rust(&p);
+
+        assert_eq!(
+            rust,
+            "color_palette!(\n    [12, 45, 67, 200],\n    [87, 212, 45],\n    [0, 0, 0, 0],\n)"
+        );
+    }
+
+    #[test]
+    fn list_dark_themes() {
+        let dark = list_themes(true, false, None);
+
+        assert!(dark.lines().any(|l| l.trim() == "Dracula"));
+        assert!(!dark.lines().any(|l| l.trim() == "AtelierCaveLight"));
+
+        let light = list_themes(false, true, None);
+
+        assert!(light.lines().any(|l| l.trim() == "AtelierCaveLight"));
+    }
+
+    #[test]
+    fn list_theme_family() {
+        let gruvbox = list_themes(true, false, Some("gruvbox"));
+
+        assert!(gruvbox.lines().any(|l| l.trim() == "GruvboxDarkHard"));
+        assert!(!gruvbox.lines().any(|l| l.trim() == "GruvboxLightHard"));
+        assert!(
+            !gruvbox
+                .lines()
+                .any(|l| l.trim() == "GruvboxMaterialDarkHard")
+        );
+    }
 }
@@ -0,0 +1,118 @@
+//! Swatch cards, rendering a palette as a grid of colored squares
+//!
+//! With a legend, the hex value of every color is written beneath its swatch, turning the card
+//! into a reference for the palette.
+use image::{Rgba, RgbaImage};
+use palette_mapper::Palette;
+
+use crate::font;
+
+/// Width and height of a single swatch
+const SWATCH_SIZE: u32 = 48;
+/// Space between swatches and around the edges of the card
+const GAP: u32 = 6;
+/// Maximum number of swatches per row
+const COLUMNS: u32 = 8;
+/// Height of the legend beneath every row of swatches
+const LEGEND_HEIGHT: u32 = font::GLYPH_HEIGHT + GAP;
+/// Size of the squares of the checkerboard behind transparent swatches
+const CHECKER_SIZE: u32 = 8;
+/// Background color of the card
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// Color of the legend
+const LEGEND_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Render `palette` as rows of swatches, optionally with the hex value of each color beneath it
+///
+/// Partially transparent colors are drawn on top of a checkerboard, so their transparency is
+/// visible. Their hex value includes the alpha value.
+pub fn render(palette: &Palette, legend: bool) -> RgbaImage {
+    let count = u32::try_from(palette.len()).unwrap_or(u32::MAX);
+
+    let columns = count.clamp(1, COLUMNS);
+    let rows = count.div_ceil(COLUMNS);
+
+    let row_height = SWATCH_SIZE + GAP + if legend { LEGEND_HEIGHT } else { 0 };
+
+    let mut card = RgbaImage::from_pixel(
+        columns * (SWATCH_SIZE + GAP) + GAP,
+        rows * row_height + GAP,
+        BACKGROUND,
+    );
+
+    for (i, color) in (0..).zip(palette) {
+        let x = GAP + (i % COLUMNS) * (SWATCH_SIZE + GAP);
+        let y = GAP + (i / COLUMNS) * row_height;
+
+        for (dx, dy) in (0..SWATCH_SIZE).flat_map(|dx| (0..SWATCH_SIZE).map(move |dy| (dx, dy))) {
+            let checker = if (dx / CHECKER_SIZE + dy / CHECKER_SIZE).is_multiple_of(2) {
+                204
+            } else {
+                255
+            };
+
+            card.put_pixel(x + dx, y + dy, blend(*color, checker));
+        }
+
+        if legend {
+            font::draw_text(
+                &mut card,
+                x,
+                y + SWATCH_SIZE + GAP / 2,
+                &hex(*color),
+                1,
+                LEGEND_COLOR,
+            );
+        }
+    }
+
+    card
+}
+
+/// Draw `color` on top of an opaque gray with the value `background`
+fn blend(color: Rgba<u8>, background: u8) -> Rgba<u8> {
+    let alpha = u16::from(color[3]);
+    let background = u16::from(background);
+
+    let [r, g, b] = [color[0], color[1], color[2]].map(|c| {
+        let blended = (u16::from(c) * alpha + background * (255 - alpha) + 127) / 255;
+
+        u8::try_from(blended).unwrap_or(u8::MAX)
+    });
+
+    Rgba([r, g, b, 255])
+}
+
+/// The hex value of `color`, including the alpha value only if it isn't opaque
+fn hex(color: Rgba<u8>) -> String {
+    match color.0 {
+        [r, g, b, 255] => format!("#{r:02x}{g:02x}{b:02x}"),
+        [r, g, b, a] => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LEGEND_HEIGHT, hex, render};
+    use palette_mapper::{Palette, rgba};
+
+    #[test]
+    fn legend_height_scales_with_rows() {
+        for (colors, rows) in [(3, 1), (8, 1), (9, 2), (24, 3)] {
+            let palette =
+                Palette::from((0..colors).map(|i| rgba!(i * 10, 0, 0)).collect::<Vec<_>>());
+
+            let plain = render(&palette, false);
+            let legend = render(&palette, true);
+
+            assert_eq!(plain.width(), legend.width());
+            assert_eq!(legend.height() - plain.height(), rows * LEGEND_HEIGHT);
+        }
+    }
+
+    #[test]
+    fn hex_with_alpha() {
+        assert_eq!(hex(rgba!(255, 0, 16)), "#ff0010");
+        assert_eq!(hex(rgba!(255, 0, 16, 128)), "#ff001080");
+    }
+}
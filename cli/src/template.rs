@@ -0,0 +1,138 @@
+//! Expansion of tokens in the output path
+//!
+//! This allows routing outputs based on the input and the options used, e.g.
+//! `out/{palette}/{stem}.{ext}`.
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+/// Values the tokens of an output path template expand to
+pub struct TemplateValues<'a> {
+    /// Path of the input image
+    pub input: &'a Path,
+    /// Name of the distance algorithm used
+    pub algorithm: &'a str,
+    /// Name of the palette used
+    pub palette: &'a str,
+}
+
+/// Expand the tokens in `template`
+///
+/// Supported tokens are:
+///
+/// - `{stem}`: file name of the input without its extension
+/// - `{ext}`: extension of the input
+/// - `{parent}`: directory containing the input
+/// - `{algorithm}`: the distance algorithm
+/// - `{palette}`: name of the theme or stem of the palette file
+///
+/// If the input has no extension a trailing `.{ext}` is removed entirely.
+pub fn expand(template: &Path, values: &TemplateValues<'_>) -> Result<PathBuf> {
+    let template = template.to_string_lossy();
+
+    let ext = values.input.extension().map(|e| e.to_string_lossy());
+
+    #[allow(clippy::literal_string_with_formatting_args, reason = "False positive")]
+    let template = match (template.strip_suffix(".{ext}"), &ext) {
+        (Some(without_ext), None) => without_ext,
+        _ => &template,
+    };
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+
+        let Some(len) = rest[start..].find('}') else {
+            bail!("unclosed `{{` in output path");
+        };
+
+        let token = &rest[start + 1..start + len];
+
+        match token {
+            "stem" => out.push_str(
+                &values
+                    .input
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default(),
+            ),
+            "ext" => out.push_str(ext.as_deref().unwrap_or_default()),
+            "parent" => out.push_str(&match values.input.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+                _ => ".".into(),
+            }),
+            "algorithm" => out.push_str(values.algorithm),
+            "palette" => out.push_str(values.palette),
+            _ => bail!(
+                "unknown token `{{{token}}}` in output path. Supported tokens are: {{stem}}, \
+                 {{ext}}, {{parent}}, {{algorithm}}, {{palette}}"
+            ),
+        }
+
+        rest = &rest[start + len + 1..];
+    }
+
+    out.push_str(rest);
+
+    Ok(PathBuf::from(out))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TemplateValues, expand};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn expand_tokens() {
+        let values = TemplateValues {
+            input: Path::new("photos/city.jpg"),
+            algorithm: "CIE76",
+            palette: "Dracula",
+        };
+
+        let expand = |template: &str| expand(Path::new(template), &values).unwrap();
+
+        assert_eq!(
+            expand("out/{palette}/{stem}.{ext}"),
+            PathBuf::from("out/Dracula/city.jpg")
+        );
+        assert_eq!(
+            expand("{parent}/{stem}_{algorithm}.png"),
+            PathBuf::from("photos/city_CIE76.png")
+        );
+        assert_eq!(expand("output.{ext}"), PathBuf::from("output.jpg"));
+        assert_eq!(expand("plain.png"), PathBuf::from("plain.png"));
+    }
+
+    #[test]
+    fn expand_without_extension() {
+        let values = TemplateValues {
+            input: Path::new("city"),
+            algorithm: "CIE76",
+            palette: "Dracula",
+        };
+
+        assert_eq!(
+            expand(Path::new("output.{ext}"), &values).unwrap(),
+            PathBuf::from("output")
+        );
+        assert_eq!(
+            expand(Path::new("{parent}/{stem}.svg"), &values).unwrap(),
+            PathBuf::from("./city.svg")
+        );
+    }
+
+    #[test]
+    fn expand_invalid_tokens() {
+        let values = TemplateValues {
+            input: Path::new("city.jpg"),
+            algorithm: "CIE76",
+            palette: "Dracula",
+        };
+
+        assert!(expand(Path::new("{name}.png"), &values).is_err());
+        assert!(expand(Path::new("{stem.png"), &values).is_err());
+    }
+}
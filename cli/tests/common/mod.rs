@@ -0,0 +1,27 @@
+//! Helpers shared by the tests running the CLI
+use assert_cmd::Command;
+use image::RgbImage;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Create a new temporary directory containing `img` saved as `input` and `palette` saved as
+/// `palette.json`
+///
+/// The format of the image is chosen by the extension of `input`.
+pub fn setup(input: &str, img: &RgbImage, palette: &str) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+
+    img.save(dir.path().join(input)).unwrap();
+    std::fs::write(dir.path().join("palette.json"), palette).unwrap();
+
+    dir
+}
+
+/// Command running the CLI non-interactively in `dir` with `args`
+pub fn cmd(dir: &Path, args: &[&str]) -> Command {
+    let mut cmd = Command::cargo_bin("palette-mapper").unwrap();
+
+    cmd.current_dir(dir).args(args).arg("--non-interactive");
+
+    cmd
+}
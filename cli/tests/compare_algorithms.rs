@@ -0,0 +1,98 @@
+//! Tests for the contact sheet of `--compare-algorithms`
+mod common;
+
+use image::{RgbImage, Rgba};
+use palette_mapper::distance::Algorithms;
+use strum::EnumCount;
+
+/// Color of the input image, which is also in the palette
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+/// Number of runs of `color` in `pixels`
+fn runs(pixels: impl Iterator<Item = Rgba<u8>>, color: Rgba<u8>) -> usize {
+    let mut previous = None;
+
+    pixels
+        .filter(|&px| {
+            let start = px == color && previous != Some(color);
+            previous = Some(px);
+
+            start
+        })
+        .count()
+}
+
+#[test]
+fn one_cell_per_algorithm() {
+    // Every algorithm maps a red image to red, so the cells are the only red areas of the sheet
+    let dir = common::setup(
+        "input.png",
+        &RgbImage::from_pixel(16, 12, image::Rgb([255, 0, 0])),
+        r##"["#ff0000", "#0000ff"]"##,
+    );
+
+    common::cmd(
+        dir.path(),
+        &[
+            "input.png",
+            "-p",
+            "palette.json",
+            "-o",
+            "sheet_{algorithm}.png",
+            "--compare-algorithms",
+        ],
+    )
+    .assert()
+    .success();
+
+    let sheet = image::open(dir.path().join("sheet_all.png"))
+        .unwrap()
+        .to_rgba8();
+
+    // the first column of cells, which contains one cell per row
+    let column = sheet
+        .enumerate_pixels()
+        .find_map(|(x, y, &px)| (px == RED).then_some((x, y)))
+        .unwrap()
+        .0;
+
+    let cell_rows = (0..sheet.height())
+        .filter(|&y| *sheet.get_pixel(column, y) == RED && y > 0)
+        .filter(|&y| *sheet.get_pixel(column, y - 1) != RED)
+        .map(|y| y + 6);
+
+    let cells: usize = cell_rows
+        .map(|y| runs((0..sheet.width()).map(|x| *sheet.get_pixel(x, y)), RED))
+        .sum();
+
+    assert_eq!(cells, Algorithms::COUNT);
+
+    // the labels
+    assert!(sheet.pixels().any(|&px| px == Rgba([0, 0, 0, 255])));
+}
+
+#[test]
+fn ora_output_is_rejected() {
+    let dir = common::setup(
+        "input.png",
+        &RgbImage::from_pixel(4, 4, image::Rgb([255, 0, 0])),
+        r##"["#ff0000", "#0000ff"]"##,
+    );
+
+    let assert = common::cmd(
+        dir.path(),
+        &[
+            "input.png",
+            "-p",
+            "palette.json",
+            "-o",
+            "sheet.ora",
+            "--compare-algorithms",
+        ],
+    )
+    .assert()
+    .failure();
+
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains(".ora"));
+    assert!(!dir.path().join("sheet.ora").exists());
+}
@@ -0,0 +1,43 @@
+//! Tests for `--emit-rust`, checking the printed snippet compiles with the `color_palette!` macro
+mod common;
+
+use image::RgbImage;
+use palette_mapper::{Palette, color_palette};
+
+/// The palette file passed to the CLI
+const PALETTE: &str = r##"["#0c2d43c8", "#57d42d", "#00000000"]"##;
+
+/// The output of `--emit-rust` for [`PALETTE`]
+///
+/// The same file is included as Rust source by [`emitted_snippet_compiles`], so the printed
+/// snippet is built against the real macro.
+const EMITTED: &str = include_str!("fixtures/emitted_palette.rs");
+
+#[test]
+fn emit_rust_matches_fixture() {
+    let dir = common::setup("input.png", &RgbImage::new(1, 1), PALETTE);
+
+    let assert = common::cmd(dir.path(), &["-p", "palette.json", "--emit-rust"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        String::from_utf8_lossy(&assert.get_output().stdout),
+        EMITTED
+    );
+}
+
+#[test]
+fn emitted_snippet_compiles() {
+    let palette: Palette = include!("fixtures/emitted_palette.rs");
+
+    assert_eq!(
+        palette,
+        Palette::try_from(vec![
+            vec![12, 45, 67, 200],
+            vec![87, 212, 45, 255],
+            vec![0, 0, 0, 0],
+        ])
+        .unwrap()
+    );
+}
@@ -0,0 +1,152 @@
+//! Tests running the whole CLI, from reading the input to writing the output
+mod common;
+
+use assert_cmd::Command;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Create an input image named `input` and a palette in a new temporary directory
+///
+/// The format of the image is chosen by the extension of `input`.
+fn setup(input: &str) -> TempDir {
+    let img = RgbImage::from_fn(8, 6, |x, y| {
+        Rgb([
+            u8::try_from(x * 30).unwrap(),
+            u8::try_from(y * 40).unwrap(),
+            128,
+        ])
+    });
+
+    common::setup(input, &img, r##"["#000000", "#ffffff", "#ff0000"]"##)
+}
+
+/// Command mapping `input` in `dir`, without any palette source yet
+fn cmd(dir: &Path, input: &str) -> Command {
+    common::cmd(dir, &[input])
+}
+
+/// Open `path` and check it is a mapped version of the image created by [`setup`]
+fn assert_output(path: &Path) -> DynamicImage {
+    assert!(path.exists(), "{} wasn't written", path.display());
+
+    let img = image::open(path).unwrap();
+    assert_eq!((img.width(), img.height()), (8, 6));
+
+    img
+}
+
+#[test]
+fn palette_file() {
+    let dir = setup("input.png");
+
+    let assert = cmd(dir.path(), "input.png")
+        .args(["-p", "palette.json"])
+        .assert()
+        .success();
+
+    // nothing is printed without the interactive steps
+    assert!(assert.get_output().stdout.is_empty());
+
+    let img = assert_output(&dir.path().join("output.png")).to_rgb8();
+
+    assert!(
+        img.pixels()
+            .all(|px| [[0, 0, 0], [255, 255, 255], [255, 0, 0]].contains(&px.0))
+    );
+}
+
+#[test]
+fn base16_theme() {
+    let dir = setup("input.png");
+
+    cmd(dir.path(), "input.png")
+        .args(["--base16", "Apathy", "-o", "base16.png"])
+        .assert()
+        .success();
+
+    assert_output(&dir.path().join("base16.png"));
+}
+
+#[test]
+fn base24_theme() {
+    let dir = setup("input.png");
+
+    cmd(dir.path(), "input.png")
+        .args(["--base24", "AdventureTime", "-o", "base24.png"])
+        .assert()
+        .success();
+
+    assert_output(&dir.path().join("base24.png"));
+}
+
+#[test]
+fn theme_slots() {
+    let dir = setup("input.png");
+
+    cmd(dir.path(), "input.png")
+        .args(["--base16", "Apathy", "--slots", "0,15", "-o", "slots.png"])
+        .assert()
+        .success();
+
+    assert_output(&dir.path().join("slots.png"));
+
+    let assert = cmd(dir.path(), "input.png")
+        .args(["--base16", "Apathy", "--slots", "8,16"])
+        .assert()
+        .failure();
+
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains("slot 16"));
+
+    // slots require a theme
+    cmd(dir.path(), "input.png")
+        .args(["-p", "palette.json", "--slots", "0"])
+        .assert()
+        .failure();
+
+    assert!(!dir.path().join("output.png").exists());
+}
+
+#[test]
+fn output_extension_substitution() {
+    let dir = setup("input.bmp");
+
+    // the default output keeps the format of the input
+    cmd(dir.path(), "input.bmp")
+        .args(["-p", "palette.json"])
+        .assert()
+        .success();
+
+    let output = dir.path().join("output.bmp");
+    assert_output(&output);
+    assert_eq!(
+        image::ImageReader::open(&output)
+            .unwrap()
+            .with_guessed_format()
+            .unwrap()
+            .format(),
+        Some(ImageFormat::Bmp)
+    );
+
+    cmd(dir.path(), "input.bmp")
+        .args(["-p", "palette.json", "-o", "{stem}-mapped.{ext}"])
+        .assert()
+        .success();
+
+    assert_output(&dir.path().join("input-mapped.bmp"));
+}
+
+#[test]
+fn missing_palette_fails() {
+    let dir = setup("input.png");
+
+    cmd(dir.path(), "input.png")
+        .args(["-p", "missing.json"])
+        .assert()
+        .failure();
+
+    assert!(!dir.path().join("output.png").exists());
+
+    // a palette source is required
+    cmd(dir.path(), "input.png").assert().failure();
+}
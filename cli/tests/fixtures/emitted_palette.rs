@@ -0,0 +1,5 @@
+color_palette!(
+    [12, 45, 67, 200],
+    [87, 212, 45],
+    [0, 0, 0, 0],
+)
@@ -0,0 +1,69 @@
+//! Tests for `--input-format`
+mod common;
+
+use assert_cmd::Command;
+use image::{Rgb, RgbImage};
+use tempfile::TempDir;
+
+/// Create a PNG with a misleading `.jpg` extension and a palette in a new temporary directory
+fn setup() -> TempDir {
+    let dir = common::setup(
+        "input.png",
+        &RgbImage::from_pixel(4, 4, Rgb([200, 30, 30])),
+        r##"["#000000", "#ffffff"]"##,
+    );
+
+    std::fs::rename(dir.path().join("input.png"), dir.path().join("input.jpg")).unwrap();
+
+    dir
+}
+
+/// Command mapping `input.jpg` in `dir` to `output.png`, decoding it as `format`
+fn forced_cmd(dir: &TempDir, format: &str) -> Command {
+    common::cmd(
+        dir.path(),
+        &[
+            "input.jpg",
+            "-p",
+            "palette.json",
+            "-o",
+            "output.png",
+            "--input-format",
+            format,
+        ],
+    )
+}
+
+#[test]
+fn forced_format_is_decoded() {
+    let dir = setup();
+
+    forced_cmd(&dir, "png").assert().success();
+
+    let output = image::open(dir.path().join("output.png"))
+        .unwrap()
+        .to_rgb8();
+
+    assert!(output.pixels().all(|&px| px == Rgb([0, 0, 0])));
+}
+
+#[test]
+fn wrong_format_fails() {
+    let dir = setup();
+
+    let assert = forced_cmd(&dir, "jpeg").assert().failure();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+
+    assert!(stderr.contains("could not decode"), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+    assert!(!dir.path().join("output.png").exists());
+}
+
+#[test]
+fn unknown_format_is_rejected() {
+    let dir = setup();
+
+    let assert = forced_cmd(&dir, "doc").assert().failure();
+
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains("possible values"));
+}
@@ -0,0 +1,99 @@
+//! Tests for `--mask`
+mod common;
+
+use assert_cmd::Command;
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+use tempfile::TempDir;
+
+/// Color of the input image
+const INPUT: Rgb<u8> = Rgb([200, 30, 30]);
+
+/// Create an input image and palette in a new temporary directory
+fn setup() -> TempDir {
+    common::setup(
+        "input.png",
+        &RgbImage::from_pixel(8, 4, INPUT),
+        r##"["#000000", "#ffffff"]"##,
+    )
+}
+
+/// Command mapping `input.png` in `dir` to `output.png` using the mask at `mask`
+fn masked_cmd(dir: &TempDir, mask: &str) -> Command {
+    common::cmd(
+        dir.path(),
+        &[
+            "input.png",
+            "-p",
+            "palette.json",
+            "-o",
+            "output.png",
+            "--mask",
+            mask,
+        ],
+    )
+}
+
+#[test]
+fn only_white_half_is_mapped() {
+    let dir = setup();
+
+    GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 0 } else { 255 }]))
+        .save(dir.path().join("mask.png"))
+        .unwrap();
+
+    masked_cmd(&dir, "mask.png").assert().success();
+
+    let output = image::open(dir.path().join("output.png"))
+        .unwrap()
+        .to_rgb8();
+
+    for (x, _, &px) in output.enumerate_pixels() {
+        if x < 4 {
+            assert_eq!(px, INPUT);
+        } else {
+            assert_eq!(px, Rgb([0, 0, 0]));
+        }
+    }
+}
+
+#[test]
+fn keeps_16_bit_input() {
+    /// Color of the 16-bit input image, which can't be represented exactly with 8 bits
+    const INPUT_16: Rgb<u16> = Rgb([51413, 7717, 7719]);
+
+    let dir = setup();
+
+    ImageBuffer::from_pixel(8, 4, INPUT_16)
+        .save(dir.path().join("input.png"))
+        .unwrap();
+    GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 0 } else { 255 }]))
+        .save(dir.path().join("mask.png"))
+        .unwrap();
+
+    masked_cmd(&dir, "mask.png").assert().success();
+
+    let DynamicImage::ImageRgb16(output) = image::open(dir.path().join("output.png")).unwrap()
+    else {
+        panic!("the output should be a 16-bit RGB image");
+    };
+
+    for (x, _, &px) in output.enumerate_pixels() {
+        if x < 4 {
+            assert_eq!(px, INPUT_16);
+        } else {
+            assert_eq!(px, Rgb([0, 0, 0]));
+        }
+    }
+}
+
+#[test]
+fn invalid_mask() {
+    let dir = setup();
+
+    std::fs::write(dir.path().join("mask.png"), "not an image").unwrap();
+
+    let assert = masked_cmd(&dir, "mask.png").assert().failure();
+
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains("mask"));
+    assert!(!dir.path().join("output.png").exists());
+}
@@ -0,0 +1,72 @@
+//! Tests for how the CLI handles existing output files
+mod common;
+
+use assert_cmd::Command;
+use image::RgbImage;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Create a small input image and palette in a new temporary directory
+fn setup() -> TempDir {
+    let img = RgbImage::from_fn(4, 4, |x, y| {
+        image::Rgb([
+            u8::try_from(x * 60).unwrap(),
+            u8::try_from(y * 60).unwrap(),
+            0,
+        ])
+    });
+
+    common::setup("input.png", &img, r##"["#000000", "#ffffff"]"##)
+}
+
+/// Command mapping `input.png` in `dir` to `output.png`
+fn map_cmd(dir: &Path) -> Command {
+    common::cmd(
+        dir,
+        &["input.png", "-p", "palette.json", "-o", "output.png"],
+    )
+}
+
+#[test]
+fn no_clobber_without_existing_output() {
+    let dir = setup();
+
+    map_cmd(dir.path()).arg("--no-clobber").assert().success();
+
+    assert!(dir.path().join("output.png").exists());
+}
+
+#[test]
+fn no_clobber_with_existing_output() {
+    let dir = setup();
+    let output = dir.path().join("output.png");
+
+    std::fs::write(&output, "existing").unwrap();
+
+    let assert = map_cmd(dir.path()).arg("--no-clobber").assert().failure();
+
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains("already exists"));
+
+    // the default is not to overwrite
+    map_cmd(dir.path()).assert().failure();
+
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), "existing");
+}
+
+#[test]
+fn overwrite_existing_output() {
+    let dir = setup();
+    let output = dir.path().join("output.png");
+
+    std::fs::write(&output, "existing").unwrap();
+
+    map_cmd(dir.path()).arg("--overwrite").assert().success();
+
+    assert!(image::open(&output).is_ok());
+
+    // the last flag wins
+    map_cmd(dir.path())
+        .args(["--overwrite", "--no-clobber"])
+        .assert()
+        .failure();
+}
@@ -14,6 +14,11 @@ else
     git clone --depth 1 https://github.com/tinted-theming/schemes.git .build/schemes
 fi
 
+# Get the value of a top level field in a scheme file
+field() {
+    grep "^$1:" "$2" | sed 's/^[^:]*:[ \t]*"\(.*\)"[ \t]*#*.*/\1/'
+}
+
 convert() {
     mkdir -p "palettes/$1"
 
@@ -21,7 +26,14 @@ convert() {
         base_name=$(basename "$file")
         name="${base_name%.yaml}"
 
-        contents="$(grep "base..:" "$file" | sed 's/[ \t]*base..:[ \t]*\(.*\)"[ \t]*#*.*/\1"/' | jq -n -c '[inputs]')"
+        colors="$(grep "base..:" "$file" | sed 's/[ \t]*base..:[ \t]*\(.*\)"[ \t]*#*.*/\1"/' | jq -n -c '[inputs]')"
+
+        contents="$(jq -n -c \
+            --arg author "$(field author "$file")" \
+            --arg system "$(field system "$file")" \
+            --arg variant "$(field variant "$file")" \
+            --argjson palette "$colors" \
+            '{author: $author, system: $system, variant: $variant, palette: $palette}')"
 
         echo "$contents" >"./palettes/$1/${name}.json"
     done
@@ -2,13 +2,9 @@
 #![allow(clippy::missing_docs_in_private_items)]
 #![allow(missing_docs)]
 
-use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
 use image::{DynamicImage, ImageBuffer, Rgba};
-use palette_mapper::{
-    Palette, color_palette,
-    distance::{Algorithms, EuclideanDistance},
-    map_image_to_palette,
-};
+use palette_mapper::{Palette, color_palette, distance::Algorithms};
 use rayon::iter::ParallelIterator;
 use std::{hint::black_box, sync::LazyLock, time::Duration};
 use strum::VariantArray;
@@ -62,22 +58,177 @@ fn map_image_to_palette(c: &mut Criterion) {
                 )),
                 &input,
                 |b, &input| {
-                    let mut img = DynamicImage::from(img_buf_noise(input.0));
+                    let img = DynamicImage::from(img_buf_noise(input.0));
 
                     let palette = &TESTING_PALETTE;
 
-                    b.iter(|| {
-                        map_image_to_palette(
-                            black_box(&mut img),
-                            black_box(palette),
-                            &black_box(EuclideanDistance),
-                        );
-                    });
+                    // A fresh copy every iteration, as mapping an already mapped image is faster
+                    b.iter_batched_ref(
+                        || img.clone(),
+                        |img| {
+                            palette_mapper::map_image_to_palette(
+                                black_box(img),
+                                black_box(palette),
+                                black_box(input.1),
+                            );
+                        },
+                        BatchSize::LargeInput,
+                    );
                 },
             );
         }
     }
 }
 
-criterion_group!(benches, map_image_to_palette);
+/// Image types without a dedicated code path are mapped via an 8-bit RGBA copy
+fn fallback(c: &mut Criterion) {
+    let noise = DynamicImage::from(img_buf_noise((1080, 720)));
+
+    let inputs = [
+        ("rgba8", noise.clone()),
+        ("luma8", DynamicImage::from(noise.to_luma8())),
+        ("luma_alpha8", DynamicImage::from(noise.to_luma_alpha8())),
+        ("luma16", DynamicImage::from(noise.to_luma16())),
+        ("rgb16", DynamicImage::from(noise.to_rgb16())),
+        ("rgba16", DynamicImage::from(noise.to_rgba16())),
+        ("rgba32f", DynamicImage::from(noise.to_rgba32f())),
+    ];
+
+    let mut group = c.benchmark_group("fallback");
+    group.sample_size(20);
+    group.measurement_time(Duration::from_secs(10));
+
+    for (name, img) in inputs {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &img, |b, img| {
+            b.iter_batched_ref(
+                || img.clone(),
+                |img| {
+                    palette_mapper::map_image_to_palette(
+                        black_box(img),
+                        black_box(&TESTING_PALETTE),
+                        black_box(&Algorithms::EuclideanDistance),
+                    );
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+}
+
+/// Images with at most 256 distinct colors, such as decoded PNGs or GIFs, are mapped once per
+/// color
+///
+/// A single additional color falls back to mapping every pixel, which shows the difference.
+fn distinct_colors(c: &mut Criterion) {
+    let colors: Vec<_> = (0..=u8::MAX)
+        .map(|r| {
+            let [g, b] = rand::random::<[u8; 2]>();
+            Rgba([r, g, b, 255])
+        })
+        .collect();
+
+    let indexed =
+        ImageBuffer::from_fn(1920, 1080, |_, _| colors[usize::from(rand::random::<u8>())]);
+
+    let mut too_many = indexed.clone();
+    too_many.put_pixel(0, 0, Rgba([0, 0, 0, 254]));
+
+    let inputs = [
+        ("256 colors", DynamicImage::from(indexed)),
+        ("257 colors", DynamicImage::from(too_many)),
+    ];
+
+    let mut group = c.benchmark_group("distinct_colors");
+    group.sample_size(20);
+    group.measurement_time(Duration::from_secs(10));
+
+    for algorithm in [Algorithms::EuclideanDistance, Algorithms::CIE76] {
+        for (name, img) in &inputs {
+            group.bench_with_input(
+                BenchmarkId::from_parameter(format!("1920x1080 {name} {algorithm}")),
+                img,
+                |b, img| {
+                    b.iter_batched_ref(
+                        || img.clone(),
+                        |img| {
+                            palette_mapper::map_image_to_palette(
+                                black_box(img),
+                                black_box(&TESTING_PALETTE),
+                                black_box(&algorithm),
+                            );
+                        },
+                        BatchSize::LargeInput,
+                    );
+                },
+            );
+        }
+    }
+}
+
+/// 10,000 colors, each differing from its neighbors by 1 in a single channel
+static PATHOLOGICAL_PALETTE: LazyLock<Palette> = LazyLock::new(|| {
+    Palette::from(
+        (0..10_000_u32)
+            .map(|i| {
+                let [r, g, ..] = i.to_le_bytes();
+                Rgba([r, g, 0, 255])
+            })
+            .collect::<Vec<_>>(),
+    )
+});
+
+/// Huge palettes of nearly identical colors, as produced by generating or extracting palettes
+fn pathological(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pathological");
+    group.sample_size(20);
+    group.measurement_time(Duration::from_secs(10));
+
+    group.bench_function("dedup_near 10000", |b| {
+        b.iter_batched_ref(
+            || PATHOLOGICAL_PALETTE.clone(),
+            |palette| {
+                black_box(palette).dedup_near(black_box(1));
+            },
+            BatchSize::SmallInput,
+        );
+    });
+
+    let colors: Vec<_> = img_buf_noise((100, 10)).pixels().copied().collect();
+
+    group.bench_function("closest_color_in_palette 10000", |b| {
+        b.iter(|| {
+            for color in &colors {
+                black_box(palette_mapper::closest_color_in_palette(
+                    black_box(color),
+                    black_box(&PATHOLOGICAL_PALETTE),
+                    black_box(&Algorithms::EuclideanDistance),
+                ));
+            }
+        });
+    });
+
+    let img = DynamicImage::from(img_buf_noise((500, 500)));
+
+    group.bench_function("500x500 palette-10000", |b| {
+        b.iter_batched_ref(
+            || img.clone(),
+            |img| {
+                palette_mapper::map_image_to_palette(
+                    black_box(img),
+                    black_box(&PATHOLOGICAL_PALETTE),
+                    black_box(&Algorithms::EuclideanDistance),
+                );
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(
+    benches,
+    map_image_to_palette,
+    fallback,
+    distinct_colors,
+    pathological
+);
 criterion_main!(benches);
@@ -0,0 +1,209 @@
+//! Analysis of images before and after mapping them
+//!
+//! See [`dither_analysis`] and [`pixels_outside_palette`]
+use std::collections::HashSet;
+
+use image::{DynamicImage, GenericImageView, Rgba};
+
+use crate::palette::Palette;
+
+/// Largest per-channel difference between sampled pixels which is still considered a smooth
+/// gradient
+///
+/// Anything above this is considered an edge or detail, where banding isn't visible.
+const SMOOTH_GRADIENT_MAX: u8 = 24;
+
+/// Fraction of smooth samples above which dithering is recommended
+const SMOOTH_RATIO_THRESHOLD: f32 = 0.3;
+
+/// Number of samples taken along the shorter side of the image
+const SAMPLES_PER_SIDE: u32 = 64;
+
+/// How the content of an image is distributed, as determined by [`dither_analysis`]
+///
+/// All values are fractions of the sampled pixels and add up to `1.0`, unless the image is too
+/// small to be sampled, in which case they are all `0.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DitherAnalysis {
+    /// Pixels which are identical to their neighbors
+    pub flat: f32,
+    /// Pixels which differ only slightly from their neighbors, as in gradients
+    pub smooth: f32,
+    /// Pixels which differ strongly from their neighbors, as in edges or fine detail
+    pub detailed: f32,
+}
+
+impl DitherAnalysis {
+    /// If the image would benefit from dithering
+    ///
+    /// This is the case when a large part of the image consists of smooth gradients, which would
+    /// otherwise result in visible banding once mapped to a palette.
+    #[must_use]
+    pub fn recommends_dithering(&self) -> bool {
+        self.smooth >= SMOOTH_RATIO_THRESHOLD
+    }
+}
+
+/// Estimate whether an image would benefit from dithering, before mapping it
+///
+/// The image is sampled on a grid and the gradient magnitude at each sample is measured by
+/// comparing it to its right and lower neighbors. The distance to the neighbors grows with the size
+/// of the image, so that wide gradients, which only change by less than one step per pixel, are
+/// still detected.
+///
+/// The alpha channel is ignored.
+#[must_use]
+pub fn dither_analysis(img: &DynamicImage) -> DitherAnalysis {
+    let (width, height) = img.dimensions();
+    let step = (width.min(height) / SAMPLES_PER_SIDE).max(1);
+
+    let (mut flat, mut smooth, mut detailed) = (0_u32, 0_u32, 0_u32);
+
+    for y in (0..height.saturating_sub(step)).step_by(step as usize) {
+        for x in (0..width.saturating_sub(step)).step_by(step as usize) {
+            let px = img.get_pixel(x, y);
+
+            let gradient = channel_difference(px, img.get_pixel(x + step, y))
+                .max(channel_difference(px, img.get_pixel(x, y + step)));
+
+            match gradient {
+                0 => flat += 1,
+                1..=SMOOTH_GRADIENT_MAX => smooth += 1,
+                _ => detailed += 1,
+            }
+        }
+    }
+
+    let total = flat + smooth + detailed;
+
+    if total == 0 {
+        return DitherAnalysis {
+            flat: 0.0,
+            smooth: 0.0,
+            detailed: 0.0,
+        };
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "Only an approximate fraction is needed."
+    )]
+    let fraction = |count: u32| count as f32 / total as f32;
+
+    DitherAnalysis {
+        flat: fraction(flat),
+        smooth: fraction(smooth),
+        detailed: fraction(detailed),
+    }
+}
+
+/// The largest difference between the color channels of two pixels, ignoring alpha
+fn channel_difference(a: Rgba<u8>, b: Rgba<u8>) -> u8 {
+    (0..3).map(|i| a[i].abs_diff(b[i])).max().unwrap_or(0)
+}
+
+/// Count the pixels of `img` which aren't exactly one of the colors in `palette`
+///
+/// This validates the result of mapping an image: with the default options, every pixel of a mapped
+/// 8-bit RGB(A) image is a palette color, so the count is `0`. Pixels are compared as 8-bit RGBA,
+/// alpha included.
+///
+/// Some modes legitimately produce colors outside the palette, for which the count is meaningless:
+/// - [`MapOptions::ignore_alpha`] keeps the alpha value of each pixel
+/// - [`MapOptions::quantize_alpha`] combines palette colors with any of the palette's alpha values
+/// - [`map_image_to_palette_masked`] and [`map_supersampled`] blend between colors
+/// - Grayscale images can only hold the gray value of each palette color
+///
+/// [`MapOptions::ignore_alpha`]: crate::MapOptions::ignore_alpha
+/// [`MapOptions::quantize_alpha`]: crate::MapOptions::quantize_alpha
+/// [`map_image_to_palette_masked`]: crate::mask::map_image_to_palette_masked
+/// [`map_supersampled`]: crate::supersample::map_supersampled
+#[must_use]
+pub fn pixels_outside_palette(img: &DynamicImage, palette: &Palette) -> usize {
+    foreign_pixels(img, palette).count()
+}
+
+/// Assert that every pixel of `img` is exactly one of the colors in `palette`
+///
+/// Intended for tests and debug builds, see [`pixels_outside_palette`] for which mapping modes this
+/// applies to.
+///
+/// ## Panics
+///
+/// If any pixel isn't a palette color. The message contains the number of such pixels and the
+/// first one of them.
+pub fn assert_all_pixels_in_palette(img: &DynamicImage, palette: &Palette) {
+    let mut foreign = foreign_pixels(img, palette);
+
+    if let Some((x, y, px)) = foreign.next() {
+        let outside = foreign.count() + 1;
+
+        panic!("{outside} pixels are not in the palette, the first one is {px:?} at ({x}, {y})");
+    }
+}
+
+/// The pixels of `img` which aren't exactly one of the colors in `palette`, with their positions
+fn foreign_pixels(
+    img: &DynamicImage,
+    palette: &Palette,
+) -> impl Iterator<Item = (u32, u32, Rgba<u8>)> {
+    let colors: HashSet<_> = palette.iter().copied().collect();
+
+    img.pixels().filter(move |(_, _, px)| !colors.contains(px))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assert_all_pixels_in_palette, dither_analysis, pixels_outside_palette};
+    use crate::{color_palette, distance::EuclideanDistance, mapped_to_palette, rgba};
+    use image::{DynamicImage, RgbaImage};
+
+    #[test]
+    fn gradient_recommends_dithering() {
+        let img = RgbaImage::from_fn(512, 128, |x, _| {
+            let v = u8::try_from(x / 2).unwrap();
+
+            rgba!(v, v, v)
+        });
+
+        let analysis = dither_analysis(&DynamicImage::from(img));
+
+        assert!(analysis.recommends_dithering(), "{analysis:?}");
+    }
+
+    #[test]
+    fn flat_image_does_not_recommend_dithering() {
+        let img = RgbaImage::from_fn(512, 128, |x, _| {
+            if x < 256 {
+                rgba!(0, 0, 0)
+            } else {
+                rgba!(255, 255, 255)
+            }
+        });
+
+        let analysis = dither_analysis(&DynamicImage::from(img));
+
+        assert!(!analysis.recommends_dithering(), "{analysis:?}");
+        assert!(analysis.flat > 0.9, "{analysis:?}");
+    }
+
+    #[test]
+    fn hard_mapped_image_is_in_palette() {
+        let img = DynamicImage::from(RgbaImage::from_fn(64, 16, |x, y| {
+            rgba!(
+                u8::try_from(x * 4).unwrap(),
+                u8::try_from(y * 16).unwrap(),
+                128
+            )
+        }));
+
+        let palette = color_palette!([0, 0, 0], [255, 0, 0], [0, 0, 255], [255, 255, 255]);
+
+        assert!(pixels_outside_palette(&img, &palette) > 0);
+
+        let mapped = mapped_to_palette(&img, &palette, &EuclideanDistance);
+
+        assert_eq!(pixels_outside_palette(&mapped, &palette), 0);
+        assert_all_pixels_in_palette(&mapped, &palette);
+    }
+}
@@ -15,7 +15,7 @@ const D54_STANDARD_2_OBSERVER_TRISTIMULUS: Xyz = Xyz([0.95047, 1.0, 1.08883]);
 /// Matrix used for conversion from sRGB to XYZ
 ///
 /// This is the D65 sRGB to XYZ matrix taken from <http://brucelindbloom.com/index.html?Eqn_RGB_XYZ_Matrix.html>
-const D65_S_RGB_MATRIX: [[f32; 3]; 3] = [
+pub const D65_S_RGB_MATRIX: [[f32; 3]; 3] = [
     [0.412_456_4, 0.357_576_1, 0.180_437_5], // row
     [0.212_672_9, 0.715_152_2, 0.072_175_0], // row
     [0.019_333_9, 0.119_192, 0.950_304_1],   // row
@@ -97,15 +97,7 @@ impl TryFrom<Vec<f32>> for Xyz {
 impl From<Rgb<u8>> for Xyz {
     /// Convert from sRGB to Xyz color space
     fn from(value: Rgb<u8>) -> Self {
-        let gama_corrected = value.0.map(|v| {
-            let normalized = f32::from(v) / 255.0;
-
-            if normalized <= 0.04045 {
-                normalized / 12.92
-            } else {
-                ((normalized + 0.055) / 1.055).powf(2.4)
-            }
-        });
+        let gama_corrected = value.0.map(|v| srgb_decode(f32::from(v) / 255.0));
 
         D65_S_RGB_MATRIX
             .iter()
@@ -174,11 +166,99 @@ impl From<Rgb<u8>> for Lab {
     }
 }
 
+/// A color represented in HSV (hue, saturation, value)
+///
+/// The hue is given in degrees in the range `0..360`, saturation and value in the range `0..=1`.
+///
+/// See:
+///
+/// - <https://en.wikipedia.org/wiki/HSL_and_HSV>
+#[derive(Debug, PartialEq)]
+pub struct Hsv(pub(crate) [f32; 3]);
+
+impl From<[f32; 3]> for Hsv {
+    fn from(value: [f32; 3]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Rgb<u8>> for Hsv {
+    fn from(value: Rgb<u8>) -> Self {
+        let max = value.0.into_iter().max().expect("Rgb has three channels");
+        let min = value.0.into_iter().min().expect("Rgb has three channels");
+
+        let [red, green, blue] = value.0.map(|v| f32::from(v) / 255.0);
+        let chroma = f32::from(max - min) / 255.0;
+
+        // Comparing the u8 values avoids comparing floats for equality
+        let hue = if max == min {
+            0.0
+        } else if max == value[0] {
+            60.0 * ((green - blue) / chroma).rem_euclid(6.0)
+        } else if max == value[1] {
+            60.0 * ((blue - red) / chroma + 2.0)
+        } else {
+            60.0 * ((red - green) / chroma + 4.0)
+        };
+
+        let brightness = f32::from(max) / 255.0;
+        let saturation = if max == 0 { 0.0 } else { chroma / brightness };
+
+        Self([hue, saturation, brightness])
+    }
+}
+
+impl From<Hsv> for Rgb<u8> {
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "The values are clamped to the range of u8 before casting."
+    )]
+    fn from(value: Hsv) -> Self {
+        let [hue, saturation, brightness] = value.0;
+
+        let chroma = brightness * saturation;
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let second = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+
+        let rgb = match sector {
+            s if s < 1.0 => [chroma, second, 0.0],
+            s if s < 2.0 => [second, chroma, 0.0],
+            s if s < 3.0 => [0.0, chroma, second],
+            s if s < 4.0 => [0.0, second, chroma],
+            s if s < 5.0 => [second, 0.0, chroma],
+            _ => [chroma, 0.0, second],
+        };
+
+        let offset = brightness - chroma;
+
+        Self::from(rgb.map(|v| ((v + offset) * 255.0).round().clamp(0.0, 255.0) as u8))
+    }
+}
+
+/// Undo the sRGB transfer function, turning an encoded value in `0.0..=1.0` into a linear one
+pub fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Apply the sRGB transfer function to a linear value in `0.0..=1.0`
+pub fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055f32.mul_add(linear.powf(1.0 / 2.4), -0.055)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use image::Rgb;
 
-    use crate::conversions::{Lab, Xyz};
+    use crate::conversions::{Hsv, Lab, Xyz};
 
     macro_rules! assert_eq_within {
         ($left:expr, $right:expr) => {
@@ -264,4 +344,27 @@ mod test {
             Lab::from([30.9703, 37.3212, -2.5585])
         );
     }
+
+    #[test]
+    fn rgb_to_hsv_palevioletred() {
+        // #DB7093, the hue is only exact to three decimals as f32
+        assert_eq_within!(
+            Hsv::from(Rgb::<u8>::from([219, 112, 147])),
+            Hsv::from([340.374, 0.489, 0.859]),
+            3_u8
+        );
+    }
+
+    #[test]
+    fn hsv_roundtrip() {
+        for r in (0..=255).step_by(5) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(17) {
+                    let rgb = Rgb::<u8>::from([r, g, b]);
+
+                    assert_eq!(Rgb::<u8>::from(Hsv::from(rgb)), rgb);
+                }
+            }
+        }
+    }
 }
@@ -23,6 +23,12 @@
 //! - [`Distance`]
 //!
 //! - [`Algorithms`]
+//!
+//! ## Thread safety
+//!
+//! [`Distance`], [`Algorithms`] and all algorithms in this module are [`Send`] and [`Sync`], so
+//! they can be used from several threads at once. This is checked at compile time. Custom
+//! algorithms need to be [`Sync`] to be used with the parallel mapping functions.
 use std::marker::PhantomData;
 
 use image::Rgba;
@@ -43,6 +49,19 @@ use crate::conversions::{Lab, RgbConversionExt};
 pub trait DistanceAlgorithm {
     /// Function used to determine the distance of two colors
     fn distance(&self, left: &Rgba<u8>, right: &Rgba<u8>) -> u32;
+
+    /// Lower bound of [`DistanceAlgorithm::distance`] for colors differing by `delta` in one channel
+    ///
+    /// For any two colors, where any of the four channels differs by `delta`, the distance must be
+    /// at least the returned value. The bound must not decrease as `delta` grows.
+    ///
+    /// This allows searching a palette without calculating the distance to every single color.
+    /// Returning [`None`], which is the default, means no such bound is known.
+    fn channel_lower_bound(&self, delta: u8) -> Option<u32> {
+        let _ = delta;
+
+        None
+    }
 }
 
 /// A distance between two colors
@@ -97,6 +116,16 @@ impl<A: DistanceAlgorithm> Distance<A> {
         }
     }
 
+    /// Create a new [`Distance`] from a [lower bound](DistanceAlgorithm::channel_lower_bound) of
+    /// the algorithm
+    #[must_use]
+    pub(crate) const fn from_lower_bound(bound: u32) -> Self {
+        Self {
+            distance: bound,
+            algorithm: PhantomData,
+        }
+    }
+
     /// Create a new [`Distance`] with the minimum value
     #[must_use]
     pub const fn new_min() -> Self {
@@ -126,6 +155,70 @@ palette_mapper_macros::algorithms! {
     CIEHybrid
 }
 
+/// [`EuclideanDistance`] with a configurable weight of the alpha channel
+///
+/// The [`EuclideanDistance`] treats a difference in alpha like a difference in any color channel.
+/// Often a slightly more transparent color should still count as the same color, though, which a
+/// lower weight allows. A weight of `0` ignores alpha entirely, `1` is the same as the
+/// [`EuclideanDistance`]. Weights above `1` favor colors of similar transparency.
+///
+/// The squared alpha difference is multiplied by the weight, so a weight of `0.25` counts a
+/// difference in alpha like half that difference in a color channel.
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{closest_color_in_palette, color_palette, rgba, distance::WeightedEuclideanDistance};
+/// let palette = color_palette!([0, 0, 0, 0], [40, 40, 40]);
+///
+/// let ignore_alpha = WeightedEuclideanDistance::new(0.0);
+///
+/// assert_eq!(
+///     closest_color_in_palette(&rgba!(0, 0, 0), &palette, &ignore_alpha),
+///     Some(&rgba!(0, 0, 0, 0))
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightedEuclideanDistance {
+    /// The factor applied to the squared alpha difference
+    alpha_weight: f32,
+}
+
+impl WeightedEuclideanDistance {
+    /// Create a new [`WeightedEuclideanDistance`] weighting alpha by `alpha_weight`
+    ///
+    /// Negative weights and NaN are treated as `0`.
+    #[must_use]
+    pub const fn new(alpha_weight: f32) -> Self {
+        Self {
+            alpha_weight: alpha_weight.max(0.0),
+        }
+    }
+
+    /// The weight of the alpha channel
+    #[must_use]
+    pub const fn alpha_weight(&self) -> f32 {
+        self.alpha_weight
+    }
+}
+
+impl Default for WeightedEuclideanDistance {
+    /// The same as the [`EuclideanDistance`]
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+crate::assert_send_sync!(
+    Algorithms,
+    Distance<Algorithms>,
+    EuclideanDistance,
+    ManhattanDistance,
+    CIE76,
+    CIEHybrid,
+    WeightedEuclideanDistance
+);
+
 impl DistanceAlgorithm for EuclideanDistance {
     #[allow(clippy::eq_op, reason = "False positive")]
     fn distance(&self, left: &Rgba<u8>, right: &Rgba<u8>) -> u32 {
@@ -139,6 +232,39 @@ impl DistanceAlgorithm for EuclideanDistance {
         .try_into()
         .unwrap()
     }
+
+    fn channel_lower_bound(&self, delta: u8) -> Option<u32> {
+        Some(u32::from(delta).pow(2))
+    }
+}
+
+impl DistanceAlgorithm for WeightedEuclideanDistance {
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "The value is positive, and casting saturates for huge weights."
+    )]
+    fn distance(&self, left: &Rgba<u8>, right: &Rgba<u8>) -> u32 {
+        let delta: [u32; 4] = std::array::from_fn(|i| u32::from(left[i].abs_diff(right[i])));
+
+        let color = delta[0].pow(2) + delta[1].pow(2) + delta[2].pow(2);
+
+        f64::from(delta[3].pow(2))
+            .mul_add(f64::from(self.alpha_weight), f64::from(color))
+            .round() as u32
+    }
+
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "The value is positive and at most the squared delta."
+    )]
+    fn channel_lower_bound(&self, delta: u8) -> Option<u32> {
+        // The channel might be alpha, which counts less with weights below 1
+        let weight = f64::from(self.alpha_weight.min(1.0));
+
+        Some((f64::from(delta).powi(2) * weight).floor() as u32)
+    }
 }
 
 impl DistanceAlgorithm for ManhattanDistance {
@@ -153,6 +279,10 @@ impl DistanceAlgorithm for ManhattanDistance {
         .try_into()
         .unwrap()
     }
+
+    fn channel_lower_bound(&self, delta: u8) -> Option<u32> {
+        Some(u32::from(delta))
+    }
 }
 
 impl DistanceAlgorithm for CIE76 {
@@ -199,3 +329,52 @@ impl DistanceAlgorithm for CIEHybrid {
         val as u32
     }
 }
+
+/// Tests of the algorithms outside of [`Algorithms`], whose tests are generated by the macro
+#[cfg(test)]
+mod weighted_test {
+    use super::{DistanceAlgorithm, EuclideanDistance, WeightedEuclideanDistance};
+    use crate::rgba;
+
+    #[test]
+    fn alpha_weight_changes_ordering() {
+        let target = rgba!(100, 100, 100);
+
+        let transparent = rgba!(100, 100, 100, 0);
+        let translucent = rgba!(100, 100, 100, 155);
+        let shifted = rgba!(130, 100, 100);
+
+        let distances = |weight: f32| {
+            let algorithm = WeightedEuclideanDistance::new(weight);
+
+            [transparent, translucent, shifted].map(|c| algorithm.distance(&target, &c))
+        };
+
+        // alpha is ignored
+        assert_eq!(distances(0.0), [0, 0, 900]);
+
+        // alpha counts as much as the color channels
+        assert_eq!(distances(1.0), [65_025, 10_000, 900]);
+
+        // small alpha differences count less than the color difference, large ones more
+        let [to_transparent, to_translucent, to_shifted] = distances(0.05);
+        assert!(to_translucent < to_shifted && to_shifted < to_transparent);
+
+        // weight 1 is exactly the plain euclidean distance
+        let weighted = WeightedEuclideanDistance::default();
+        for (left, right) in [(target, rgba!(0, 255, 3, 7)), (rgba!(1, 2, 3, 4), target)] {
+            assert_eq!(
+                weighted.distance(&left, &right),
+                EuclideanDistance.distance(&left, &right)
+            );
+        }
+
+        // invalid weights ignore alpha
+        for weight in [-1.0, f32::NAN] {
+            assert_eq!(
+                WeightedEuclideanDistance::new(weight).distance(&target, &transparent),
+                0
+            );
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Mapping with error diffusion dithering
+//!
+//! Instead of mapping every pixel on its own, the difference (error) between a pixel and the
+//! palette color it is mapped to is spread to the neighboring pixels, which haven't been mapped
+//! yet. Over an area, the mapped colors then average out to the original colors, which avoids
+//! banding in gradients.
+//!
+//! The error is diffused with the [Floyd–Steinberg](https://en.wikipedia.org/wiki/Floyd%E2%80%93Steinberg_dithering)
+//! weights.
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+
+use crate::{
+    distance::DistanceAlgorithm,
+    error::Error,
+    options::Snap,
+    palette::Palette,
+    search::{AutoSearch, NearestSearch},
+};
+
+/// Options for [`map_image_to_palette_dithered`]
+///
+/// The [`Default`] options diffuse the full error.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DitherOptions {
+    /// See [`DitherOptions::error_clamp`]
+    error_clamp: Option<u8>,
+    /// See [`DitherOptions::snap_tolerance`]
+    snap_tolerance: Option<u8>,
+}
+
+impl DitherOptions {
+    /// Limit the error diffused from every pixel to at most `max` per channel
+    ///
+    /// Along sharp edges between very different colors, the error of a pixel can be large. Once it
+    /// is spread into the flat region on the other side of the edge, it shows up as a halo of
+    /// speckles following the edge. Clamping the error keeps it from crossing such edges, at the
+    /// cost of less accurate averages in gradients.
+    ///
+    /// A flat region of a palette color is free of halos if `max` is below half the difference
+    /// between that color and the next palette color, per channel. Too small values stop areas
+    /// from being dithered at all, since the error then never adds up enough to reach the next
+    /// palette color.
+    #[must_use]
+    pub const fn error_clamp(mut self, max: u8) -> Self {
+        self.error_clamp = Some(max);
+        self
+    }
+
+    /// Snap pixels which are nearly identical to a palette color directly to that color
+    ///
+    /// Which pixels are snapped is the same as for
+    /// [`MapOptions::snap_tolerance`](crate::MapOptions::snap_tolerance). Snapped pixels neither
+    /// take on the error of their neighbors nor diffuse their own, so regions made up of (nearly)
+    /// exact palette colors stay flat, even next to dithered areas.
+    #[must_use]
+    pub const fn snap_tolerance(mut self, tolerance: u8) -> Self {
+        self.snap_tolerance = Some(tolerance);
+        self
+    }
+}
+
+/// The Floyd–Steinberg weights, as offsets from the current pixel and the fraction of the error
+const FLOYD_STEINBERG: [(isize, usize, f32); 4] = [
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+/// Map `img` to `palette`, diffusing the error of every pixel to its neighbors
+///
+/// Only the red, green and blue values are diffused, the alpha value of each pixel is matched as
+/// is. Rows are processed from top to bottom and left to right, so unlike
+/// [`map_image_to_palette`](crate::map_image_to_palette) this can't be parallelized.
+///
+/// The color type of the image is kept.
+///
+/// ## Errors
+///
+/// [`Error::EmptyPalette`] if `palette` doesn't contain any colors. The image is not changed.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "The width of the image always fits into usize."
+)]
+pub fn map_image_to_palette_dithered<D: DistanceAlgorithm>(
+    img: &mut DynamicImage,
+    palette: &Palette,
+    algorithm: &D,
+    options: &DitherOptions,
+) -> Result<(), Error> {
+    if palette.is_empty() {
+        return Err(Error::EmptyPalette);
+    }
+
+    let search = AutoSearch::new(palette, algorithm);
+    let (width, height) = img.dimensions();
+    let row_len = usize::try_from(width).expect("u32 fits in usize");
+
+    let clamp = options.error_clamp.map_or(f32::INFINITY, f32::from);
+    let snap = options
+        .snap_tolerance
+        .map(|tolerance| Snap::new(palette, tolerance));
+
+    // the accumulated error of the current and the next row
+    let mut errors = [vec![[0.0_f32; 3]; row_len], vec![[0.0_f32; 3]; row_len]];
+
+    for y in 0..height {
+        for (x, column) in (0..width).zip(0..row_len) {
+            let px = img.get_pixel(x, y);
+
+            if let Some(snapped) = snap.as_ref().and_then(|snap| snap.snap(px)) {
+                img.put_pixel(x, y, snapped);
+                continue;
+            }
+
+            let error = errors[0][column];
+
+            let mut target = px;
+            for (channel, error) in target.0.iter_mut().zip(error) {
+                #[allow(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_sign_loss,
+                    reason = "The value is clamped to the range of u8 before casting."
+                )]
+                {
+                    *channel = (f32::from(*channel) + error).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+
+            let closest: Rgba<u8> = *search
+                .nearest(target, algorithm)
+                .ok_or(Error::EmptyPalette)?;
+
+            img.put_pixel(x, y, closest);
+
+            let diffused: [f32; 3] = std::array::from_fn(|channel| {
+                (f32::from(target[channel]) - f32::from(closest[channel])).clamp(-clamp, clamp)
+            });
+
+            for (dx, dy, weight) in FLOYD_STEINBERG {
+                let Some(nx) = column.checked_add_signed(dx).filter(|&nx| nx < row_len) else {
+                    continue;
+                };
+
+                for (acc, error) in errors[dy][nx].iter_mut().zip(diffused) {
+                    *acc = error.mul_add(weight, *acc);
+                }
+            }
+        }
+
+        errors.swap(0, 1);
+        errors[1].fill([0.0; 3]);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DitherOptions, map_image_to_palette_dithered};
+    use crate::{color_palette, distance::EuclideanDistance, error::Error, rgba};
+    use image::{DynamicImage, GenericImageView, RgbaImage, imageops};
+
+    /// A light gray region next to a dark gray one, with a hard edge between them
+    ///
+    /// The dark gray is part of the palette.
+    fn edge_image() -> DynamicImage {
+        DynamicImage::from(RgbaImage::from_fn(64, 64, |x, _| {
+            if x < 32 {
+                rgba!(170, 170, 170)
+            } else {
+                rgba!(64, 64, 64)
+            }
+        }))
+    }
+
+    /// Number of pixels in the dark region of [`edge_image`] which changed by dithering
+    fn halo(img: &DynamicImage) -> usize {
+        img.pixels()
+            .filter(|&(x, _, px)| x >= 32 && px != rgba!(64, 64, 64))
+            .count()
+    }
+
+    /// Number of pixels in the light region of [`edge_image`] which weren't mapped to white
+    fn dithered(img: &DynamicImage) -> usize {
+        img.pixels()
+            .filter(|&(x, _, px)| x < 32 && px != rgba!(255, 255, 255))
+            .count()
+    }
+
+    #[test]
+    fn clamped_error_prevents_halos() {
+        let palette = color_palette!([0, 0, 0], [64, 64, 64], [255, 255, 255]);
+
+        let mut unclamped = edge_image();
+        map_image_to_palette_dithered(
+            &mut unclamped,
+            &palette,
+            &EuclideanDistance,
+            &DitherOptions::default(),
+        )
+        .unwrap();
+
+        let mut clamped = edge_image();
+        map_image_to_palette_dithered(
+            &mut clamped,
+            &palette,
+            &EuclideanDistance,
+            &DitherOptions::default().error_clamp(24),
+        )
+        .unwrap();
+
+        assert!(halo(&unclamped) > 0);
+        assert_eq!(halo(&clamped), 0);
+
+        // the light region is still dithered
+        assert!(dithered(&clamped) > 0);
+
+        let mut comparison = RgbaImage::new(128, 64);
+        imageops::replace(&mut comparison, &unclamped.to_rgba8(), 0, 0);
+        imageops::replace(&mut comparison, &clamped.to_rgba8(), 64, 0);
+
+        let mut buf = Vec::new();
+        crate::encoding::write_png(&DynamicImage::from(comparison), &mut buf).unwrap();
+
+        insta::assert_binary_snapshot!(".png", buf);
+    }
+
+    #[test]
+    fn snapped_region_stays_flat() {
+        let palette = color_palette!([0, 0, 0], [100, 100, 100], [255, 255, 255]);
+
+        // a horizontal gradient next to a flat region close to a palette color
+        let img = DynamicImage::from(RgbaImage::from_fn(64, 64, |x, _| {
+            if x < 32 {
+                let value = u8::try_from(x * 8).unwrap();
+                rgba!(value, value, value)
+            } else {
+                rgba!(104, 103, 104)
+            }
+        }));
+
+        let flat = |img: &DynamicImage| {
+            img.pixels()
+                .filter(|&(x, _, _)| x >= 32)
+                .all(|(_, _, px)| px == rgba!(100, 100, 100))
+        };
+
+        let mut unsnapped = img.clone();
+        map_image_to_palette_dithered(
+            &mut unsnapped,
+            &palette,
+            &EuclideanDistance,
+            &DitherOptions::default(),
+        )
+        .unwrap();
+
+        let mut snapped = img;
+        map_image_to_palette_dithered(
+            &mut snapped,
+            &palette,
+            &EuclideanDistance,
+            &DitherOptions::default().snap_tolerance(4),
+        )
+        .unwrap();
+
+        // the error of the flat region adds up to speckles unless it is snapped
+        assert!(!flat(&unsnapped));
+        assert!(flat(&snapped));
+
+        // the gradient is still dithered, mixing colors within a column
+        assert!(
+            (0..32).any(|x| (1..64).any(|y| snapped.get_pixel(x, y) != snapped.get_pixel(x, 0)))
+        );
+    }
+
+    #[test]
+    fn empty_palette() {
+        let mut img = edge_image();
+
+        assert!(matches!(
+            map_image_to_palette_dithered(
+                &mut img,
+                &crate::Palette::default(),
+                &EuclideanDistance,
+                &DitherOptions::default()
+            ),
+            Err(Error::EmptyPalette)
+        ));
+        assert_eq!(img, edge_image());
+    }
+}
@@ -0,0 +1,67 @@
+//! Reproducible encoding of images
+//!
+//! The defaults of the encoders used by [`DynamicImage::save`] are allowed to change between
+//! versions of `image` and `png`. The helpers in this module pin every setting explicitly instead,
+//! so the same image keeps being encoded to the same bytes. This matters for snapshot tests and for
+//! outputs checked into version control.
+use std::io::Write;
+
+use image::{
+    DynamicImage,
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+};
+
+use crate::error::Error;
+
+/// Encode `img` as a PNG with fixed encoder settings
+///
+/// The image is compressed with `fdeflate` and every row uses the Paeth filter. No ancillary
+/// chunks (such as color profiles or timestamps) are written. Unlike the zlib levels, `fdeflate`
+/// doesn't depend on which zlib backend other crates enable, so the output is the same in every
+/// build. The bytes may still change if `fdeflate` itself changes.
+///
+/// ## Errors
+///
+/// [`Error::Io`] if encoding or writing fails.
+pub fn write_png<W: Write>(img: &DynamicImage, writer: W) -> Result<(), Error> {
+    let encoder = PngEncoder::new_with_quality(writer, CompressionType::Fast, FilterType::Paeth);
+
+    img.write_with_encoder(encoder)
+        .map_err(std::io::Error::other)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_png;
+    use crate::rgba;
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+
+    #[test]
+    fn png_encoding_is_byte_stable() {
+        let img = DynamicImage::from(RgbaImage::from_fn(37, 23, |x, y| {
+            rgba!(
+                u8::try_from(x * 7).unwrap(),
+                u8::try_from(y * 11).unwrap(),
+                u8::try_from(x * y % 256).unwrap(),
+                200
+            )
+        }));
+
+        let encode = || {
+            let mut buf = Vec::new();
+            write_png(&img, &mut buf).unwrap();
+
+            buf
+        };
+
+        let first = encode();
+
+        assert_eq!(first, encode());
+        assert_eq!(
+            image::load_from_memory_with_format(&first, ImageFormat::Png).unwrap(),
+            img
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! The error type used throughout the library
+//!
+//! See [`Error`]
+use thiserror::Error;
+
+use crate::conversions::ConversionError;
+
+/// Errors returned by the checked APIs of this library
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The palette doesn't contain any colors, so nothing can be mapped to it
+    #[error("The palette doesn't contain any colors.")]
+    EmptyPalette,
+    /// A value could not be converted to a color
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+    /// The data passed could not be interpreted as a palette
+    #[error("Invalid palette data: {0}")]
+    InvalidPaletteData(String),
+    /// The operation was cancelled via a [`CancellationToken`](crate::options::CancellationToken)
+    #[error("The operation was cancelled.")]
+    Cancelled,
+    /// Reading or writing failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+    use crate::{
+        Palette, conversions::ConversionError, distance::EuclideanDistance,
+        try_map_image_to_palette,
+    };
+    use image::{DynamicImage, RgbaImage};
+
+    #[test]
+    fn empty_palette_error() {
+        let mut img = DynamicImage::from(RgbaImage::new(2, 2));
+
+        assert!(matches!(
+            try_map_image_to_palette(&mut img, &Palette::default(), &EuclideanDistance),
+            Err(Error::EmptyPalette)
+        ));
+    }
+
+    #[test]
+    fn conversion_error() {
+        assert!(matches!(
+            Palette::try_from(vec![vec![1, 2, 3], vec![1, 2]]),
+            Err(Error::Conversion(ConversionError::InputTooShort))
+        ));
+
+        assert!(matches!(
+            Palette::try_from(vec![vec![1, 2, 3, 4, 5]]),
+            Err(Error::Conversion(ConversionError::InputTooLong))
+        ));
+    }
+
+    #[test]
+    fn invalid_palette_data_error() {
+        assert!(matches!(
+            "#ff00ff #nothex".parse::<Palette>(),
+            Err(Error::InvalidPaletteData(_))
+        ));
+    }
+}
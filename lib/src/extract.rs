@@ -0,0 +1,366 @@
+//! Extracting a palette from an image
+//!
+//! Palettes are extracted with the [median cut](https://en.wikipedia.org/wiki/Median_cut)
+//! algorithm: starting with a single box containing every color of the image, the box spanning the
+//! widest range in one channel is repeatedly split at the median of that channel, until there are
+//! as many boxes as colors requested. Every box then contributes the average of its colors,
+//! weighted by how often they occur in the image.
+//!
+//! See [`extract_palette`], [`extract_palette_with`] and [`suggest_palette_size`]
+use std::collections::HashMap;
+
+use image::{DynamicImage, GenericImageView, Rgba};
+
+use crate::{
+    distance::DistanceAlgorithm,
+    palette::Palette,
+    search::{AutoSearch, NearestSearch},
+};
+
+/// Largest palette size tried by [`suggest_palette_size`]
+pub const MAX_SUGGESTED_SIZE: usize = 256;
+
+/// Options for [`extract_palette_with`]
+///
+/// The [`Default`] options are the same as [`extract_palette`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractOptions {
+    /// See [`ExtractOptions::sort_by_frequency`]
+    sort_by_frequency: bool,
+}
+
+impl ExtractOptions {
+    /// Order the colors by how many pixels of the image they cover, the most common color first
+    ///
+    /// Every extracted color covers the pixels whose colors were averaged into it. Colors covering
+    /// the same number of pixels keep their order. This puts the dominant color of the image
+    /// first, which is usually the one to use as a background when theming.
+    #[must_use]
+    pub const fn sort_by_frequency(mut self, sort: bool) -> Self {
+        self.sort_by_frequency = sort;
+        self
+    }
+}
+
+/// Extract a palette of at most `count` colors from `img`
+///
+/// See [`extract_palette_with`] for ordering the colors by frequency.
+///
+/// Images with fewer than `count` distinct colors result in a palette of exactly those colors.
+/// All four channels, including alpha, are taken into account. The result is deterministic, but
+/// the order of the colors isn't meaningful.
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{extract::extract_palette, rgba};
+/// # use image::{DynamicImage, RgbaImage};
+/// let img = DynamicImage::from(RgbaImage::from_fn(8, 8, |x, _| {
+///     if x < 4 { rgba!(255, 0, 0) } else { rgba!(0, 0, 255) }
+/// }));
+///
+/// let palette = extract_palette(&img, 2);
+///
+/// assert_eq!(palette.len(), 2);
+/// assert!(palette.iter().any(|c| *c == rgba!(255, 0, 0)));
+/// ```
+#[must_use]
+pub fn extract_palette(img: &DynamicImage, count: usize) -> Palette {
+    extract_palette_with(img, count, &ExtractOptions::default())
+}
+
+/// Extract a palette of at most `count` colors from `img`, as configured by `options`
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{extract::{extract_palette_with, ExtractOptions}, rgba};
+/// # use image::{DynamicImage, RgbaImage};
+/// // mostly blue
+/// let img = DynamicImage::from(RgbaImage::from_fn(8, 8, |x, _| {
+///     if x < 2 { rgba!(255, 0, 0) } else { rgba!(0, 0, 255) }
+/// }));
+///
+/// let palette = extract_palette_with(&img, 2, &ExtractOptions::default().sort_by_frequency(true));
+///
+/// assert_eq!(palette.iter().next(), Some(&rgba!(0, 0, 255)));
+/// ```
+#[must_use]
+pub fn extract_palette_with(img: &DynamicImage, count: usize, options: &ExtractOptions) -> Palette {
+    if count == 0 {
+        return Palette::default();
+    }
+
+    let mut cut = MedianCut::new(img);
+
+    while cut.boxes.len() < count && cut.split() {}
+
+    if options.sort_by_frequency {
+        // stable, so boxes of the same population keep their order
+        cut.boxes
+            .sort_by_key(|colors| std::cmp::Reverse(colors.iter().map(|(_, n)| n).sum::<u64>()));
+    }
+
+    cut.palette()
+}
+
+/// Find the smallest palette size for which the palette extracted from `img` maps it with a mean
+/// distance of at most `max_mean_distance`
+///
+/// The mean distance is the average over all pixels of the [distance](DistanceAlgorithm) between
+/// the pixel and the color it is mapped to. A size of `0` is returned for empty images, and
+/// [`MAX_SUGGESTED_SIZE`] if even that many colors don't reach the threshold.
+///
+/// ## Cost
+///
+/// Since median cut only ever splits boxes, the palettes of all sizes are extracted in a single
+/// pass. But the image is mapped to each of them, so in the worst case the image's distinct
+/// colors are mapped [`MAX_SUGGESTED_SIZE`] times, to palettes of up to that many colors. This is
+/// far slower than a single [`extract_palette`], especially with expensive algorithms and
+/// thresholds which are only met by large palettes.
+#[must_use]
+pub fn suggest_palette_size<D: DistanceAlgorithm>(
+    img: &DynamicImage,
+    max_mean_distance: f64,
+    algorithm: &D,
+) -> usize {
+    let mut cut = MedianCut::new(img);
+
+    if cut.boxes.is_empty() {
+        return 0;
+    }
+
+    loop {
+        let palette = cut.palette();
+
+        if palette.len() >= MAX_SUGGESTED_SIZE
+            || cut.mean_distance(&palette, algorithm) <= max_mean_distance
+            || !cut.split()
+        {
+            return palette.len();
+        }
+    }
+}
+
+/// A distinct color of an image, with the number of pixels of that color
+type Entry = (Rgba<u8>, u64);
+
+/// The state of the median cut algorithm
+struct MedianCut {
+    /// The boxes, each containing at least one distinct color
+    boxes: Vec<Vec<Entry>>,
+}
+
+impl MedianCut {
+    /// Start with a single box containing all colors of `img`, or no box if `img` is empty
+    fn new(img: &DynamicImage) -> Self {
+        let mut histogram = HashMap::<Rgba<u8>, u64>::new();
+
+        for (_, _, px) in img.pixels() {
+            *histogram.entry(px).or_default() += 1;
+        }
+
+        let mut colors: Vec<_> = histogram.into_iter().collect();
+
+        // the order of the histogram is random, which mustn't leak into the result
+        colors.sort_unstable_by_key(|(c, _)| c.0);
+
+        Self {
+            boxes: if colors.is_empty() {
+                Vec::new()
+            } else {
+                vec![colors]
+            },
+        }
+    }
+
+    /// Split the box with the widest range in a single channel at the median of that channel
+    ///
+    /// Returns `false` if every box only contains a single color, so there is nothing to split.
+    fn split(&mut self) -> bool {
+        let Some((index, channel, _)) = self
+            .boxes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, colors)| {
+                let (channel, range) = widest_channel(colors);
+
+                (range > 0).then_some((index, channel, range))
+            })
+            // the first of equally wide boxes, to stay deterministic
+            .min_by_key(|&(index, _, range)| (std::cmp::Reverse(range), index))
+        else {
+            return false;
+        };
+
+        let colors = &mut self.boxes[index];
+        colors.sort_unstable_by_key(|(c, _)| (c[channel], c.0));
+
+        let total: u64 = colors.iter().map(|(_, n)| n).sum();
+
+        let mut seen = 0;
+        let median = colors
+            .iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen * 2 >= total
+            })
+            .unwrap_or(0);
+
+        // both halves must keep at least one color
+        let at = (median + 1).clamp(1, colors.len() - 1);
+
+        let upper = colors.split_off(at);
+        self.boxes.push(upper);
+
+        true
+    }
+
+    /// The palette made up of the weighted average color of every box
+    fn palette(&self) -> Palette {
+        Palette::from(
+            self.boxes
+                .iter()
+                .map(|colors| average(colors))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// The mean distance of every pixel to the color in `palette` it is mapped to
+    fn mean_distance<D: DistanceAlgorithm>(&self, palette: &Palette, algorithm: &D) -> f64 {
+        let search = AutoSearch::new(palette, algorithm);
+
+        let (sum, total) =
+            self.boxes
+                .iter()
+                .flatten()
+                .fold((0_u64, 0_u64), |(sum, total), (color, n)| {
+                    let distance = search
+                        .nearest(*color, algorithm)
+                        .map_or(0, |closest| algorithm.distance(color, closest));
+
+                    (sum + u64::from(distance) * n, total + n)
+                });
+
+        #[allow(
+            clippy::cast_precision_loss,
+            reason = "Only an approximate mean is needed."
+        )]
+        let mean = sum as f64 / total.max(1) as f64;
+
+        mean
+    }
+}
+
+/// The channel with the widest range of values in `colors`, with that range
+fn widest_channel(colors: &[Entry]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let (min, max) = colors
+                .iter()
+                .fold((u8::MAX, u8::MIN), |(min, max), (c, _)| {
+                    (min.min(c[channel]), max.max(c[channel]))
+                });
+
+            (channel, max.saturating_sub(min))
+        })
+        // the first of equally wide channels
+        .min_by_key(|&(channel, range)| (std::cmp::Reverse(range), channel))
+        .unwrap_or((0, 0))
+}
+
+/// The average of `colors`, weighted by how often each occurs
+fn average(colors: &[Entry]) -> Rgba<u8> {
+    let total: u64 = colors.iter().map(|(_, n)| n).sum::<u64>().max(1);
+
+    let sums = colors.iter().fold([0_u64; 4], |mut sums, (c, n)| {
+        sums.iter_mut()
+            .zip(c.0)
+            .for_each(|(s, v)| *s += u64::from(v) * n);
+
+        sums
+    });
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "The mean of u8 values fits in a u8."
+    )]
+    Rgba::from(sums.map(|sum| ((sum + total / 2) / total) as u8))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExtractOptions, extract_palette, extract_palette_with, suggest_palette_size};
+    use crate::{distance::EuclideanDistance, rgba};
+    use image::{DynamicImage, RgbaImage};
+
+    #[test]
+    fn extract_distinct_colors() {
+        let colors = [
+            rgba!(255, 0, 0),
+            rgba!(0, 255, 0),
+            rgba!(0, 0, 255),
+            rgba!(255, 255, 255, 0),
+        ];
+
+        let img = DynamicImage::from(RgbaImage::from_fn(16, 16, |x, y| {
+            colors[(x / 8 + y / 8 * 2) as usize]
+        }));
+
+        let palette = extract_palette(&img, 4);
+        assert_eq!(palette.len(), 4);
+        assert!(colors.iter().all(|c| palette.iter().any(|p| p == c)));
+
+        // asking for more colors than there are doesn't invent any
+        assert_eq!(extract_palette(&img, 10), palette);
+        assert_eq!(extract_palette(&img, 1).len(), 1);
+        assert!(extract_palette(&img, 0).is_empty());
+    }
+
+    #[test]
+    fn dominant_color_first() {
+        // 70% green, the rest split between red and blue
+        let img = DynamicImage::from(RgbaImage::from_fn(10, 10, |x, _| match x {
+            0..7 => rgba!(0, 200, 0),
+            7 | 8 => rgba!(255, 0, 0),
+            _ => rgba!(0, 0, 255),
+        }));
+
+        let sorted = ExtractOptions::default().sort_by_frequency(true);
+
+        let palette = extract_palette_with(&img, 3, &sorted);
+
+        assert_eq!(
+            palette.iter().copied().collect::<Vec<_>>(),
+            [rgba!(0, 200, 0), rgba!(255, 0, 0), rgba!(0, 0, 255)]
+        );
+
+        // the same colors as without sorting
+        let mut unsorted = extract_palette(&img, 3);
+        assert_eq!(
+            unsorted.sort_by_key(|c| c.0),
+            extract_palette_with(&img, 3, &sorted).sort_by_key(|c| c.0)
+        );
+    }
+
+    #[test]
+    fn two_colors_suffice_for_two_color_image() {
+        let img = DynamicImage::from(RgbaImage::from_fn(32, 32, |x, y| {
+            if (x + y).is_multiple_of(3) {
+                rgba!(20, 40, 200)
+            } else {
+                rgba!(240, 200, 10)
+            }
+        }));
+
+        assert_eq!(suggest_palette_size(&img, 0.5, &EuclideanDistance), 2);
+
+        // anything is close enough to a single average color
+        assert_eq!(suggest_palette_size(&img, f64::MAX, &EuclideanDistance), 1);
+
+        assert_eq!(
+            suggest_palette_size(&DynamicImage::new_rgba8(0, 0), 0.5, &EuclideanDistance),
+            0
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Indexed (paletted) images
+//!
+//! Once an image is mapped to a palette, every pixel is one of the palette's colors. Storing the
+//! palette once, together with an index into it for every pixel, is both smaller and exact. The
+//! encoders used by [`DynamicImage::save`] don't know about the palette, so they either store
+//! full colors (PNG) or derive a new palette by quantizing the image again (GIF).
+//!
+//! [`IndexedImage`] hands the known palette and indices straight to the encoders instead.
+use std::{borrow::Cow, collections::HashMap, io::Write};
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::{Palette, error::Error};
+
+/// An image made up of indices into a palette of at most 256 colors
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{color_palette, distance::EuclideanDistance, indexed::IndexedImage, map_image_to_palette};
+/// # use image::{DynamicImage, RgbaImage};
+/// let mut img = DynamicImage::from(RgbaImage::new(4, 4));
+/// let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+///
+/// map_image_to_palette(&mut img, &palette, &EuclideanDistance);
+///
+/// let indexed = IndexedImage::from_mapped(&img, &palette).unwrap();
+///
+/// let mut png = Vec::new();
+/// indexed.write_png(&mut png)?;
+/// # Ok::<(), palette_mapper::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedImage {
+    /// Width in pixels
+    width: u32,
+    /// Height in pixels
+    height: u32,
+    /// The index into [`Self::palette`] of every pixel, row by row
+    indices: Vec<u8>,
+    /// The colors referenced by [`Self::indices`]
+    palette: Palette,
+}
+
+impl IndexedImage {
+    /// Create an indexed image from an image which has already been mapped to `palette`
+    ///
+    /// Each pixel is looked up in the palette. If the palette contains a color multiple times, the
+    /// first one is used.
+    ///
+    /// Returns [`None`] if the palette contains more than 256 colors or a pixel isn't exactly one
+    /// of the palette's colors, for example because the alpha values of the image were kept using
+    /// [`MapOptions::ignore_alpha`](crate::MapOptions::ignore_alpha).
+    #[must_use]
+    pub fn from_mapped(img: &DynamicImage, palette: &Palette) -> Option<Self> {
+        if palette.len() > 256 {
+            return None;
+        }
+
+        let mut lookup: HashMap<Rgba<u8>, u8> = HashMap::with_capacity(palette.len());
+
+        for (i, color) in (0..=u8::MAX).zip(palette) {
+            lookup.entry(*color).or_insert(i);
+        }
+
+        let indices = img
+            .pixels()
+            .map(|(_, _, px)| lookup.get(&px).copied())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            width: img.width(),
+            height: img.height(),
+            indices,
+            palette: palette.clone(),
+        })
+    }
+
+    /// The index into the [palette](Self::palette) of every pixel, row by row
+    #[must_use]
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    /// The palette the [indices](Self::indices) refer to
+    #[must_use]
+    pub const fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Convert back to a regular image
+    #[must_use]
+    pub fn to_rgba8(&self) -> RgbaImage {
+        let colors: Vec<_> = self.palette.iter().copied().collect();
+
+        let mut indices = self.indices.iter();
+
+        RgbaImage::from_fn(self.width, self.height, |_, _| {
+            indices
+                .next()
+                .map_or(Rgba([0; 4]), |&i| colors[usize::from(i)])
+        })
+    }
+
+    /// Encode the image as an indexed PNG
+    ///
+    /// The smallest bit depth able to hold all indices is used. Transparency is stored for every
+    /// palette color, so alpha values are kept exactly.
+    ///
+    /// The encoder settings are the same as in `encoding::write_png`: they are all fixed, and the
+    /// rows are filtered with Paeth and compressed with `fdeflate`, so the output doesn't depend
+    /// on the zlib backend enabled for `flate2`.
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::Io`] if encoding or writing fails.
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let depth = match self.palette.len() {
+            0..=2 => png::BitDepth::One,
+            3..=4 => png::BitDepth::Two,
+            5..=16 => png::BitDepth::Four,
+            _ => png::BitDepth::Eight,
+        };
+
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(depth);
+        encoder.set_deflate_compression(png::DeflateCompression::FdeflateUltraFast);
+        encoder.set_filter(png::Filter::Paeth);
+        encoder.set_palette(
+            self.palette
+                .iter()
+                .flat_map(|c| [c[0], c[1], c[2]])
+                .collect::<Vec<_>>(),
+        );
+
+        if !self.palette.is_opaque() {
+            encoder.set_trns(self.palette.iter().map(|c| c[3]).collect::<Vec<_>>());
+        }
+
+        encoder
+            .write_header()
+            .and_then(|mut w| w.write_image_data(&self.packed_rows(depth as u8)))
+            .map_err(std::io::Error::other)?;
+
+        Ok(())
+    }
+
+    /// Encode the image as a GIF
+    ///
+    /// GIF only supports a single fully transparent color, so the first palette color with an
+    /// alpha value of 0 is used as such. All other alpha values are ignored.
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::Io`] if the image is larger than 65535 pixels in either dimension (the maximum
+    /// GIF supports), or encoding or writing fails.
+    pub fn write_gif<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let too_large = |_| std::io::Error::other("image is too large for GIF");
+
+        let width = u16::try_from(self.width).map_err(too_large)?;
+        let height = u16::try_from(self.height).map_err(too_large)?;
+
+        let rgb: Vec<u8> = self
+            .palette
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect();
+
+        let frame = gif::Frame {
+            width,
+            height,
+            buffer: Cow::Borrowed(&self.indices),
+            transparent: (0..=u8::MAX)
+                .zip(&self.palette)
+                .find_map(|(i, c)| (c[3] == 0).then_some(i)),
+            ..gif::Frame::default()
+        };
+
+        gif::Encoder::new(writer, width, height, &rgb)
+            .and_then(|mut encoder| encoder.write_frame(&frame))
+            .map_err(std::io::Error::other)?;
+
+        Ok(())
+    }
+
+    /// The indices packed into rows of `bits` bits per pixel, as expected by PNG
+    fn packed_rows(&self, bits: u8) -> Cow<'_, [u8]> {
+        if bits == 8 {
+            return Cow::Borrowed(&self.indices);
+        }
+
+        let per_byte = usize::from(8 / bits);
+        let width = usize::try_from(self.width).expect("u32 fits in usize");
+
+        Cow::Owned(
+            self.indices
+                .chunks(width.max(1))
+                .flat_map(|row| {
+                    row.chunks(per_byte).map(|pixels| {
+                        pixels
+                            .iter()
+                            .zip(1..)
+                            .fold(0, |byte, (&index, i)| byte | index << (8 - bits * i))
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IndexedImage;
+    use crate::{color_palette, distance::EuclideanDistance, map_image_to_palette, rgba};
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+
+    /// A mapped gradient and the palette it was mapped to
+    fn mapped_gradient(palette: &crate::Palette) -> DynamicImage {
+        let mut img = DynamicImage::from(RgbaImage::from_fn(320, 240, |x, y| {
+            rgba!(
+                u8::try_from(x * 255 / 319).unwrap(),
+                u8::try_from(y * 255 / 239).unwrap(),
+                100
+            )
+        }));
+
+        map_image_to_palette(&mut img, palette, &EuclideanDistance);
+
+        img
+    }
+
+    #[test]
+    fn indexed_png_smaller_and_exact() {
+        for palette in [
+            color_palette!([0, 0, 0], [255, 255, 255]),
+            color_palette!([0, 0, 0], [255, 0, 0], [0, 255, 0, 128]),
+            color_palette!(
+                [0, 0, 0],
+                [255, 0, 0],
+                [0, 255, 0],
+                [0, 0, 255],
+                [255, 255, 0],
+                [100, 100, 100, 0]
+            ),
+        ] {
+            let img = mapped_gradient(&palette);
+
+            let mut naive = Vec::new();
+            crate::encoding::write_png(&img, &mut naive).unwrap();
+
+            let indexed = IndexedImage::from_mapped(&img, &palette).unwrap();
+            assert_eq!(indexed.to_rgba8(), img.to_rgba8());
+
+            let mut direct = Vec::new();
+            indexed.write_png(&mut direct).unwrap();
+
+            assert!(
+                direct.len() < naive.len(),
+                "{} >= {}",
+                direct.len(),
+                naive.len()
+            );
+
+            let decoded = image::load_from_memory_with_format(&direct, ImageFormat::Png).unwrap();
+
+            assert_eq!(decoded.to_rgba8(), img.to_rgba8());
+        }
+    }
+
+    #[test]
+    fn indexed_gif_exact() {
+        let palette = color_palette!([0, 0, 0, 0], [255, 0, 0], [0, 255, 0], [12, 34, 56]);
+        let img = mapped_gradient(&palette);
+
+        let mut direct = Vec::new();
+        IndexedImage::from_mapped(&img, &palette)
+            .unwrap()
+            .write_gif(&mut direct)
+            .unwrap();
+
+        let decoded = image::load_from_memory_with_format(&direct, ImageFormat::Gif).unwrap();
+
+        assert_eq!(decoded.to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn from_mapped_requires_palette_colors() {
+        let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+
+        let img = DynamicImage::from(RgbaImage::from_pixel(2, 2, rgba!(1, 2, 3)));
+
+        assert_eq!(IndexedImage::from_mapped(&img, &palette), None);
+    }
+}
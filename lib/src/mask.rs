@@ -0,0 +1,266 @@
+//! Mapping only parts of an image, controlled by a mask
+//!
+//! The mask is a grayscale image, where white means the pixel is fully mapped and black means it
+//! keeps its original color. The values in between blend the two, so soft brush strokes in a mask
+//! result in soft transitions.
+use image::{
+    DynamicImage, GenericImage, GenericImageView, GrayImage, ImageBuffer, Pixel, RgbaImage,
+    imageops,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    MapOptions, distance::DistanceAlgorithm, error::Error, map_image_to_palette_with,
+    palette::Palette,
+};
+
+/// Map `img` to `palette`, blending the result with the original according to `mask`
+///
+/// Every pixel is blended with its mapped color by the luma of the corresponding mask pixel: `255`
+/// results in the mapped color, `0` in the original color. If the mask doesn't have the same
+/// dimensions as the image, it is resized to match, which may blur it.
+///
+/// The color type of the image is kept and pixels of 8-bit, 16-bit and floating point images are
+/// blended at their full precision, so pixels where the mask is black keep their exact original
+/// values. Blended pixels are generally not in the palette.
+///
+/// ## Errors
+///
+/// See [`map_image_to_palette_with`]
+pub fn map_image_to_palette_masked<D: DistanceAlgorithm + Sync>(
+    img: &mut DynamicImage,
+    palette: &Palette,
+    algorithm: &D,
+    mask: &GrayImage,
+    options: &MapOptions,
+) -> Result<(), Error> {
+    let original = img.clone();
+
+    map_image_to_palette_with(img, palette, algorithm, options)?;
+
+    let mask = if mask.dimensions() == img.dimensions() {
+        std::borrow::Cow::Borrowed(mask)
+    } else {
+        std::borrow::Cow::Owned(imageops::resize(
+            mask,
+            img.width(),
+            img.height(),
+            imageops::FilterType::Triangle,
+        ))
+    };
+    let strength = |i: usize| mask.as_raw()[i];
+
+    match (img, &original) {
+        (DynamicImage::ImageLuma8(to), DynamicImage::ImageLuma8(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageLumaA8(to), DynamicImage::ImageLumaA8(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageRgb8(to), DynamicImage::ImageRgb8(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageRgba8(to), DynamicImage::ImageRgba8(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageLuma16(to), DynamicImage::ImageLuma16(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageLumaA16(to), DynamicImage::ImageLumaA16(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageRgb16(to), DynamicImage::ImageRgb16(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageRgba16(to), DynamicImage::ImageRgba16(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageRgb32F(to), DynamicImage::ImageRgb32F(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        (DynamicImage::ImageRgba32F(to), DynamicImage::ImageRgba32F(from)) => {
+            blend_buffers(from, to, strength);
+        }
+        // Mapping keeps the color type, so this is only reached for color types added to `image`
+        // in the future. These are blended at 8-bit precision and written back pixel by pixel,
+        // which keeps the color type of the mapped image.
+        (img, original) => {
+            let mut blended = img.to_rgba8();
+            blend_buffers(&original.to_rgba8(), &mut blended, strength);
+
+            for (x, y, px) in blended.enumerate_pixels() {
+                img.put_pixel(x, y, *px);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Blend every pixel of `from` with the corresponding pixel of `to` by `strength`
+///
+/// `0` results in `from`, `255` in `to`, like a mask with that value everywhere. This is how the
+/// mapped image can be shown at a reduced opacity over the original. Both images must have the
+/// same dimensions.
+///
+/// ## Panics
+///
+/// If the dimensions of the images differ.
+#[must_use]
+pub fn blend_images(from: &DynamicImage, to: &DynamicImage, strength: u8) -> RgbaImage {
+    assert_eq!(
+        from.dimensions(),
+        to.dimensions(),
+        "Only images of the same size can be blended."
+    );
+
+    let mut blended = to.to_rgba8();
+    blend_buffers(&from.to_rgba8(), &mut blended, |_| strength);
+
+    blended
+}
+
+/// Blend every pixel of `from` into the corresponding pixel of `to`, by the strength returned for
+/// the pixel's index
+///
+/// Both buffers must have the same dimensions.
+fn blend_buffers<P: Pixel>(
+    from: &ImageBuffer<P, Vec<P::Subpixel>>,
+    to: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    strength: impl Fn(usize) -> u8 + Sync,
+) where
+    P::Subpixel: Blend + Send + Sync,
+{
+    let channels = usize::from(P::CHANNEL_COUNT);
+
+    #[cfg(feature = "rayon")]
+    let pixels = to.par_chunks_mut(channels).zip(from.par_chunks(channels));
+    #[cfg(not(feature = "rayon"))]
+    let pixels = to.chunks_mut(channels).zip(from.chunks(channels));
+
+    pixels.enumerate().for_each(|(i, (to, from))| {
+        let strength = strength(i);
+
+        for (to, &from) in to.iter_mut().zip(from) {
+            *to = Blend::blend(from, *to, strength);
+        }
+    });
+}
+
+/// A channel type which can be blended
+trait Blend: Copy {
+    /// Blend from `from` to `to` by `strength`, where `0` results in exactly `from` and `255` in
+    /// exactly `to`
+    fn blend(from: Self, to: Self, strength: u8) -> Self;
+}
+
+impl Blend for u8 {
+    fn blend(from: Self, to: Self, strength: u8) -> Self {
+        let strength = u16::from(strength);
+        let blended = (u16::from(from) * (255 - strength) + u16::from(to) * strength + 127) / 255;
+
+        Self::try_from(blended).unwrap_or(Self::MAX)
+    }
+}
+
+impl Blend for u16 {
+    fn blend(from: Self, to: Self, strength: u8) -> Self {
+        let strength = u32::from(strength);
+        let blended = (u32::from(from) * (255 - strength) + u32::from(to) * strength + 127) / 255;
+
+        Self::try_from(blended).unwrap_or(Self::MAX)
+    }
+}
+
+impl Blend for f32 {
+    fn blend(from: Self, to: Self, strength: u8) -> Self {
+        let strength = Self::from(strength) / 255.0;
+
+        from.mul_add(1.0 - strength, to * strength)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{blend_images, map_image_to_palette_masked};
+    use crate::{MapOptions, color_palette, distance::EuclideanDistance, rgba};
+    use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgb, RgbaImage};
+
+    #[test]
+    fn mask_controls_strength() {
+        let color = rgba!(200, 30, 30);
+        let original = DynamicImage::from(RgbaImage::from_pixel(6, 2, color));
+
+        // black, gray and white thirds
+        let mask = GrayImage::from_fn(6, 2, |x, _| Luma([[0, 128, 255][x as usize / 2]]));
+
+        let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+
+        let mut img = original.clone();
+        map_image_to_palette_masked(
+            &mut img,
+            &palette,
+            &EuclideanDistance,
+            &mask,
+            &MapOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(img.get_pixel(0, 0), color);
+        assert_eq!(img.get_pixel(3, 1), rgba!(100, 15, 15));
+        assert_eq!(img.get_pixel(5, 0), rgba!(0, 0, 0));
+
+        // a smaller mask is scaled up to match
+        let mut img = original;
+        map_image_to_palette_masked(
+            &mut img,
+            &palette,
+            &EuclideanDistance,
+            &GrayImage::from_pixel(1, 1, Luma([255])),
+            &MapOptions::default(),
+        )
+        .unwrap();
+
+        assert!(img.pixels().all(|(_, _, px)| px == rgba!(0, 0, 0)));
+    }
+
+    #[test]
+    fn mask_keeps_16_bit_precision() {
+        let color = Rgb([51_234_u16, 7_777, 4_001]);
+        let mut img = DynamicImage::ImageRgb16(image::ImageBuffer::from_pixel(3, 1, color));
+
+        let mask = GrayImage::from_fn(3, 1, |x, _| Luma([[0, 128, 255][x as usize]]));
+
+        map_image_to_palette_masked(
+            &mut img,
+            &color_palette!([0, 0, 0], [255, 255, 255]),
+            &EuclideanDistance,
+            &mask,
+            &MapOptions::default(),
+        )
+        .unwrap();
+
+        let DynamicImage::ImageRgb16(img) = img else {
+            panic!("color type changed")
+        };
+
+        assert_eq!(*img.get_pixel(0, 0), color);
+        // blended in 16 bits, instead of the 8-bit (99, 15, 8) scaled up
+        assert_eq!(*img.get_pixel(1, 0), Rgb([25_517, 3_873, 1_993]));
+        assert_eq!(*img.get_pixel(2, 0), Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn blend_whole_images() {
+        let from = DynamicImage::from(RgbaImage::from_pixel(3, 2, rgba!(200, 30, 30)));
+        let to = DynamicImage::from(RgbaImage::from_pixel(3, 2, rgba!(0, 0, 0)));
+
+        assert_eq!(blend_images(&from, &to, 0), from.to_rgba8());
+        assert_eq!(blend_images(&from, &to, 255), to.to_rgba8());
+        assert_eq!(
+            blend_images(&from, &to, 128).get_pixel(2, 1),
+            &rgba!(100, 15, 15)
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! Options changing how an image is mapped to a palette
+//!
+//! The main type is [`MapOptions`], which is passed to [`map_image_to_palette_with`].
+//!
+//! [`map_image_to_palette_with`]: crate::map_image_to_palette_with
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use image::{DynamicImage, Rgb32FImage, Rgba, Rgba32FImage};
+
+use crate::{
+    conversions,
+    palette::{LuminanceCoefficients, Palette},
+};
+
+/// Options for mapping an image to a palette
+///
+/// The [`Default`] options result in the same behavior as [`map_image_to_palette`].
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{MapOptions, color_palette, distance::EuclideanDistance, map_image_to_palette_with};
+/// # use image::{DynamicImage, RgbaImage};
+/// let mut img = DynamicImage::from(RgbaImage::new(4, 4));
+/// let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+///
+/// let options = MapOptions::default().snap_tolerance(2);
+///
+/// map_image_to_palette_with(&mut img, &palette, &EuclideanDistance, &options)?;
+/// # Ok::<(), palette_mapper::Error>(())
+/// ```
+///
+/// [`map_image_to_palette`]: crate::map_image_to_palette
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MapOptions {
+    /// See [`MapOptions::snap_tolerance`]
+    pub(crate) snap_tolerance: Option<u8>,
+    /// See [`MapOptions::ignore_alpha`]
+    pub(crate) ignore_alpha: bool,
+    /// See [`MapOptions::cancellation_token`]
+    pub(crate) cancellation_token: Option<CancellationToken>,
+    /// See [`MapOptions::tone_map`]
+    pub(crate) tone_map: Option<ToneMap>,
+    /// See [`MapOptions::monotone`]
+    pub(crate) monotone: bool,
+    /// See [`MapOptions::luminance_coefficients`]
+    pub(crate) luminance_coefficients: LuminanceCoefficients,
+    /// See [`MapOptions::quantize_alpha`]
+    pub(crate) quantize_alpha: bool,
+}
+
+crate::assert_send_sync!(MapOptions, CancellationToken);
+
+impl MapOptions {
+    /// Allow stopping the mapping early by cancelling `token`
+    ///
+    /// The token is checked once for every row of the image. After it has been cancelled the
+    /// mapping returns [`Error::Cancelled`](crate::Error::Cancelled), leaving the image partially
+    /// mapped.
+    #[must_use]
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Match colors only by their red, green and blue values, keeping each pixel's alpha value
+    ///
+    /// This is usually what is wanted when mapping transparent images to opaque palettes.
+    ///
+    /// See [`Palette::closest_color_rgb_ignoring_alpha`]
+    #[must_use]
+    pub const fn ignore_alpha(mut self, ignore: bool) -> Self {
+        self.ignore_alpha = ignore;
+        self
+    }
+
+    /// Match the alpha value separately from the color, snapping it to the palette's alpha levels
+    ///
+    /// Colors are matched only by their red, green and blue values, like with
+    /// [`MapOptions::ignore_alpha`]. The alpha value of each pixel is then replaced by the closest
+    /// alpha value found in the palette, preferring the more opaque one if two are equally close.
+    /// This allows any palette color to be used with any of the palette's transparency levels,
+    /// which is useful for stylized semi-transparent output.
+    ///
+    /// Takes precedence over [`MapOptions::ignore_alpha`].
+    #[must_use]
+    pub const fn quantize_alpha(mut self, quantize: bool) -> Self {
+        self.quantize_alpha = quantize;
+        self
+    }
+
+    /// Snap pixels which are nearly identical to a palette color directly to that color
+    ///
+    /// A pixel is snapped if none of its channels (including alpha) differ by more than
+    /// `tolerance` from a palette color. If several colors qualify, the one with the smallest
+    /// summed channel difference is used, so exact matches always win. All other pixels are
+    /// mapped as usual.
+    ///
+    /// This keeps regions made up of (nearly) exact palette colors flat, regardless of the
+    /// distance algorithm.
+    #[must_use]
+    pub const fn snap_tolerance(mut self, tolerance: u8) -> Self {
+        self.snap_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Map every pixel to the palette color closest in luminance, preserving the order of tones
+    ///
+    /// A pixel darker than another is then never mapped to a brighter color than the other one,
+    /// which plain nearest-color matching can't guarantee. This keeps grayscale and other tonal
+    /// images legible. Hue and saturation are ignored entirely, so this is best suited for
+    /// palettes ordered by brightness, such as grayscale ramps. The distance algorithm is not
+    /// used.
+    ///
+    /// Pixels [snapped](Self::snap_tolerance) to a palette color don't follow this order.
+    ///
+    /// See [`LuminanceSearch`](crate::search::LuminanceSearch)
+    #[must_use]
+    pub const fn monotone(mut self, monotone: bool) -> Self {
+        self.monotone = monotone;
+        self
+    }
+
+    /// Weight the color channels by `coefficients` wherever the luminance of a color is compared
+    ///
+    /// Currently this only affects [`MapOptions::monotone`]. Defaults to
+    /// [`LuminanceCoefficients::Rec709`].
+    #[must_use]
+    pub const fn luminance_coefficients(mut self, coefficients: LuminanceCoefficients) -> Self {
+        self.luminance_coefficients = coefficients;
+        self
+    }
+
+    /// Tone map floating point (HDR) images before mapping them
+    ///
+    /// Without this, values of floating point images outside of `0.0..=1.0` are clipped, losing
+    /// all detail in the highlights. With it, the values are treated as linear light (as is the
+    /// case for e.g. `.exr` files) and compressed into `0.0..=1.0` using `operator`, before being
+    /// encoded as sRGB.
+    ///
+    /// Since palette colors are 8-bit, the image is replaced by an 8-bit image in the process.
+    /// Other images aren't affected.
+    #[must_use]
+    pub const fn tone_map(mut self, operator: ToneMap) -> Self {
+        self.tone_map = Some(operator);
+        self
+    }
+}
+
+/// Tone mapping operators, compressing HDR values into the displayable range
+///
+/// See [`MapOptions::tone_map`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMap {
+    /// The simple Reinhard operator `x / (1 + x)`
+    ///
+    /// Never fully reaches white, preserving detail in even the brightest highlights.
+    Reinhard,
+    /// An approximation of the ACES filmic curve
+    ///
+    /// Has more contrast than [`ToneMap::Reinhard`], with very bright values reaching white.
+    Aces,
+}
+
+impl ToneMap {
+    /// Map a single linear value to `0.0..=1.0`
+    fn apply(self, value: f32) -> f32 {
+        let value = value.max(0.0);
+
+        match self {
+            Self::Reinhard => value / (1.0 + value),
+            // Krzysztof Narkowicz's fit of the ACES curve
+            Self::Aces => (value * 2.51f32.mul_add(value, 0.03)
+                / value.mul_add(2.43f32.mul_add(value, 0.59), 0.14))
+            .clamp(0.0, 1.0),
+        }
+    }
+
+    /// Tone map and sRGB encode a single linear value
+    fn encode(self, value: f32) -> f32 {
+        conversions::srgb_encode(self.apply(value))
+    }
+}
+
+/// Tone map `img` to an 8-bit image, if it is a floating point image
+///
+/// Returns `None` for all other images. See [`MapOptions::tone_map`]
+pub(crate) fn tone_map_image(img: &DynamicImage, operator: ToneMap) -> Option<DynamicImage> {
+    match img {
+        DynamicImage::ImageRgb32F(buf) => {
+            let mut buf: Rgb32FImage = buf.clone();
+
+            buf.pixels_mut()
+                .for_each(|px| px.0 = px.0.map(|v| operator.encode(v)));
+
+            Some(DynamicImage::from(DynamicImage::from(buf).into_rgb8()))
+        }
+        DynamicImage::ImageRgba32F(buf) => {
+            let mut buf: Rgba32FImage = buf.clone();
+
+            buf.pixels_mut().for_each(|px| {
+                let [r, g, b, a] = px.0;
+
+                px.0 = [
+                    operator.encode(r),
+                    operator.encode(g),
+                    operator.encode(b),
+                    a,
+                ];
+            });
+
+            Some(DynamicImage::from(DynamicImage::from(buf).into_rgba8()))
+        }
+        _ => None,
+    }
+}
+
+/// A token used to cooperatively cancel a long running operation
+///
+/// Cloned tokens share their state, so cancelling one clone cancels all of them.
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::options::CancellationToken;
+/// let token = CancellationToken::new();
+/// let for_worker = token.clone();
+///
+/// token.cancel();
+///
+/// assert!(for_worker.is_cancelled());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new token, which isn't cancelled
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token and all its clones
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token has been cancelled
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    /// Use an existing flag as token, where `true` means cancelled
+    fn from(value: Arc<AtomicBool>) -> Self {
+        Self(value)
+    }
+}
+
+impl PartialEq for CancellationToken {
+    /// Tokens are equal if they are clones of each other
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+/// Finds the color a pixel is [snapped](MapOptions::snap_tolerance) to
+///
+/// Which palette colors are within the tolerance is computed up front for every value of every
+/// channel, as a bit set with one bit per color. The colors a pixel may snap to are then found
+/// with a lookup per channel, instead of comparing the pixel to every color of the palette.
+#[derive(Debug, Clone)]
+pub(crate) struct Snap {
+    /// The palette's colors
+    colors: Vec<Rgba<u8>>,
+    /// For every channel, the colors within the tolerance of each channel value
+    ///
+    /// The bit sets of the values are stored one after another, each made up of `words` words.
+    within: [Vec<u64>; 4],
+    /// Number of words in each bit set
+    words: usize,
+}
+
+impl Snap {
+    /// Create a new [`Snap`] for `palette`, snapping colors whose channels all differ by at most
+    /// `tolerance` from a palette color
+    pub(crate) fn new(palette: &Palette, tolerance: u8) -> Self {
+        let words = palette.len().div_ceil(64);
+        let mut within: [Vec<u64>; 4] = std::array::from_fn(|_| vec![0; 256 * words]);
+
+        for (i, color) in palette.iter().enumerate() {
+            for (sets, &channel) in within.iter_mut().zip(&color.0) {
+                for value in channel.saturating_sub(tolerance)..=channel.saturating_add(tolerance) {
+                    sets[usize::from(value) * words + i / 64] |= 1 << (i % 64);
+                }
+            }
+        }
+
+        Self {
+            colors: palette.iter().copied().collect(),
+            within,
+            words,
+        }
+    }
+
+    /// Find the closest palette color whose channels all differ by at most the tolerance from
+    /// `color`
+    ///
+    /// Colors are compared by their summed channel differences. Ties go to the first color.
+    pub(crate) fn snap(&self, color: Rgba<u8>) -> Option<Rgba<u8>> {
+        let sets: [&[u64]; 4] = std::array::from_fn(|channel| {
+            let start = usize::from(color[channel]) * self.words;
+
+            &self.within[channel][start..start + self.words]
+        });
+
+        (0..self.words)
+            .flat_map(|word| {
+                let mut bits = sets.iter().fold(u64::MAX, |bits, set| bits & set[word]);
+
+                // the indices of the set bits, from lowest to highest
+                std::iter::from_fn(move || {
+                    (bits != 0).then(|| {
+                        let bit =
+                            usize::try_from(bits.trailing_zeros()).expect("u32 fits in usize");
+                        bits &= bits - 1;
+
+                        word * 64 + bit
+                    })
+                })
+            })
+            .map(|i| self.colors[i])
+            .min_by_key(|pcolor| {
+                color
+                    .0
+                    .iter()
+                    .zip(pcolor.0)
+                    .map(|(c, p)| u16::from(c.abs_diff(p)))
+                    .sum::<u16>()
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CancellationToken, MapOptions, Snap, ToneMap};
+    use crate::{
+        Error, Palette, color_palette,
+        distance::{CIE76, DistanceAlgorithm, EuclideanDistance},
+        map_image_to_palette_with, rgba,
+    };
+    use image::{DynamicImage, Rgb32FImage, Rgba, RgbaImage};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn monotone_preserves_tone_order() {
+        let palette = color_palette!([0, 0, 0], [0, 0, 255], [255, 255, 255]);
+
+        // The blue is darker than the gray, but closer to a brighter palette color
+        let darker = rgba!(0, 0, 200);
+        let brighter = rgba!(58, 58, 58);
+
+        let luminance = |c: Rgba<u8>| crate::conversions::Xyz::from(c).0[1];
+        assert!(luminance(darker) < luminance(brighter));
+
+        let map = |options: &MapOptions| {
+            let mut img = RgbaImage::from_fn(32, 32, |x, y| {
+                rgba!(
+                    u8::try_from(x * 8).unwrap(),
+                    u8::try_from(y * 8).unwrap(),
+                    u8::try_from(x * y % 256).unwrap()
+                )
+            });
+            img.put_pixel(0, 0, darker);
+            img.put_pixel(1, 0, brighter);
+
+            let original = img.clone();
+            let mut img = DynamicImage::from(img);
+
+            map_image_to_palette_with(&mut img, &palette, &EuclideanDistance, options).unwrap();
+
+            original
+                .pixels()
+                .zip(img.to_rgba8().pixels())
+                .map(|(&input, &output)| (luminance(input), luminance(output)))
+                .collect::<Vec<_>>()
+        };
+
+        let inverted = |mut pairs: Vec<(f32, f32)>| {
+            pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            pairs.windows(2).any(|w| w[0].1 > w[1].1)
+        };
+
+        // nearest-color inverts the tones
+        assert!(inverted(map(&MapOptions::default())));
+        assert!(!inverted(map(&MapOptions::default().monotone(true))));
+    }
+
+    #[test]
+    fn quantize_alpha_to_palette_levels() {
+        let palette = color_palette!([255, 0, 0, 0], [0, 0, 255, 128], [0, 255, 0]);
+
+        let alphas = [0, 40, 63, 64, 65, 128, 180, 191, 192, 255];
+
+        let mut img = DynamicImage::from(RgbaImage::from_fn(10, 1, |x, _| {
+            rgba!(250, 10, 10, alphas[x as usize])
+        }));
+
+        map_image_to_palette_with(
+            &mut img,
+            &palette,
+            &EuclideanDistance,
+            &MapOptions::default().quantize_alpha(true),
+        )
+        .unwrap();
+
+        let img = img.to_rgba8();
+
+        // the color is matched independently of the alpha value
+        assert!(img.pixels().all(|c| c.0[..3] == [255, 0, 0]));
+        assert_eq!(
+            img.pixels().map(|c| c[3]).collect::<Vec<_>>(),
+            [0, 0, 0, 128, 128, 128, 128, 128, 255, 255]
+        );
+    }
+
+    #[test]
+    fn quantize_alpha_keeps_monotone_and_snapping() {
+        let map = |palette: &Palette, pixel: Rgba<u8>, options: &MapOptions| {
+            let mut img = DynamicImage::from(RgbaImage::from_pixel(1, 1, pixel));
+
+            map_image_to_palette_with(&mut img, palette, &EuclideanDistance, options).unwrap();
+
+            *img.to_rgba8().get_pixel(0, 0)
+        };
+
+        let quantize = MapOptions::default().quantize_alpha(true);
+
+        // the blue is closer to the gray, but closer in luminance to the black
+        let palette = color_palette!([0, 0, 0, 128], [100, 100, 100]);
+        let blue = rgba!(0, 0, 200);
+
+        assert_eq!(map(&palette, blue, &quantize), rgba!(100, 100, 100));
+        assert_eq!(
+            map(&palette, blue, &quantize.clone().monotone(true)),
+            rgba!(0, 0, 0)
+        );
+
+        // the pixel is closer to the second color, but snaps to the first one
+        let palette = color_palette!([100, 100, 100, 0], [103, 101, 101]);
+        let pixel = rgba!(102, 100, 100);
+
+        assert_eq!(map(&palette, pixel, &quantize), rgba!(103, 101, 101));
+        assert_eq!(
+            map(&palette, pixel, &quantize.snap_tolerance(2)),
+            rgba!(100, 100, 100)
+        );
+    }
+
+    /// Snap `color` to a color in `palette`, see [`Snap::snap`]
+    fn snap(color: Rgba<u8>, palette: &Palette, tolerance: u8) -> Option<Rgba<u8>> {
+        Snap::new(palette, tolerance).snap(color)
+    }
+
+    #[test]
+    fn snap_near_exact() {
+        let palette = color_palette!([0, 0, 0], [100, 100, 100], [101, 100, 100]);
+
+        assert_eq!(
+            snap(rgba!(101, 99, 100), &palette, 2),
+            Some(rgba!(101, 100, 100))
+        );
+        assert_eq!(
+            snap(rgba!(101, 100, 100), &palette, 2),
+            Some(rgba!(101, 100, 100))
+        );
+        assert_eq!(
+            snap(rgba!(100, 100, 100), &palette, 2),
+            Some(rgba!(100, 100, 100))
+        );
+        // ties go to the first color
+        assert_eq!(
+            snap(
+                rgba!(101, 100, 100),
+                &color_palette!([100, 100, 100], [102, 100, 100]),
+                2
+            ),
+            Some(rgba!(100, 100, 100))
+        );
+        assert_eq!(snap(rgba!(0, 0, 0), &palette, 0), Some(rgba!(0, 0, 0)));
+        assert_eq!(snap(rgba!(150, 100, 100), &palette, 2), None);
+        assert_eq!(snap(rgba!(0, 0, 0, 250), &palette, 2), None);
+    }
+
+    #[test]
+    fn snap_large_palette() {
+        // more colors than fit into a single word of the bit sets
+        let palette = Palette::from((0..200).map(|i| rgba!(i, i, i)).collect::<Vec<_>>());
+
+        assert_eq!(
+            snap(rgba!(150, 151, 149), &palette, 2),
+            Some(rgba!(150, 150, 150))
+        );
+        assert_eq!(
+            snap(rgba!(199, 201, 199), &palette, 2),
+            Some(rgba!(199, 199, 199))
+        );
+        assert_eq!(snap(rgba!(150, 160, 150), &palette, 2), None);
+    }
+
+    #[test]
+    fn snap_tolerance_mapping() {
+        // CIE76 ignores alpha, so without snapping the first color is always chosen over the
+        // second one
+        let palette = color_palette!([100, 100, 100, 0], [100, 100, 100], [255, 0, 0]);
+
+        let mut img = RgbaImage::new(2, 1);
+        img.put_pixel(0, 0, rgba!(101, 100, 99));
+        img.put_pixel(1, 0, rgba!(140, 100, 100));
+
+        let mut img = DynamicImage::from(img);
+
+        map_image_to_palette_with(
+            &mut img,
+            &palette,
+            &CIE76,
+            &MapOptions::default().snap_tolerance(2),
+        )
+        .unwrap();
+
+        let img = img.into_rgba8();
+
+        assert_eq!(*img.get_pixel(0, 0), rgba!(100, 100, 100));
+        assert_eq!(*img.get_pixel(1, 0), rgba!(100, 100, 100, 0));
+    }
+
+    #[test]
+    fn ignore_alpha_mapping() {
+        let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+
+        let mut img = RgbaImage::new(2, 1);
+        img.put_pixel(0, 0, rgba!(10, 20, 30, 0));
+        img.put_pixel(1, 0, rgba!(240, 230, 250, 128));
+
+        let mut img = DynamicImage::from(img);
+
+        map_image_to_palette_with(
+            &mut img,
+            &palette,
+            &EuclideanDistance,
+            &MapOptions::default().ignore_alpha(true),
+        )
+        .unwrap();
+
+        let img = img.into_rgba8();
+
+        assert_eq!(*img.get_pixel(0, 0), rgba!(0, 0, 0, 0));
+        assert_eq!(*img.get_pixel(1, 0), rgba!(255, 255, 255, 128));
+    }
+
+    #[test]
+    fn tone_map_compresses_highlights() {
+        let palette = Palette::from((0..=255).map(|v| rgba!(v, v, v)).collect::<Vec<Rgba<u8>>>());
+
+        let img = Rgb32FImage::from_fn(4, 1, |x, _| {
+            let v = [0.5, 1.0, 2.0, 4.0][x as usize];
+
+            image::Rgb([v, v, v])
+        });
+
+        let map = |options: &MapOptions| {
+            let mut img = DynamicImage::from(img.clone());
+
+            map_image_to_palette_with(&mut img, &palette, &EuclideanDistance, options).unwrap();
+
+            img.into_rgba8()
+                .pixels()
+                .map(|px| px[0])
+                .collect::<Vec<_>>()
+        };
+
+        // without tone mapping everything above 1.0 is clipped
+        let clipped = map(&MapOptions::default());
+        assert_eq!(clipped[1..], [255, 255, 255]);
+
+        for operator in [ToneMap::Reinhard, ToneMap::Aces] {
+            let mapped = map(&MapOptions::default().tone_map(operator));
+
+            assert!(mapped.is_sorted(), "{operator:?}: {mapped:?}");
+            assert!(mapped[1] < mapped[2], "{operator:?}: {mapped:?}");
+        }
+
+        let reinhard = map(&MapOptions::default().tone_map(ToneMap::Reinhard));
+        assert!(reinhard[3] < 255, "{reinhard:?}");
+    }
+
+    /// Cancels the token after a set amount of distance calculations
+    struct CancelAfter {
+        /// The token to cancel
+        token: CancellationToken,
+        /// Distance calculations done so far
+        calls: AtomicU32,
+        /// Cancel after this many calculations
+        limit: u32,
+    }
+
+    impl DistanceAlgorithm for CancelAfter {
+        fn distance(&self, left: &Rgba<u8>, right: &Rgba<u8>) -> u32 {
+            if self.calls.fetch_add(1, Ordering::Relaxed) >= self.limit {
+                self.token.cancel();
+            }
+
+            EuclideanDistance.distance(left, right)
+        }
+    }
+
+    #[test]
+    fn cancel_mid_map() {
+        let token = CancellationToken::new();
+        let algorithm = CancelAfter {
+            token: token.clone(),
+            calls: AtomicU32::new(0),
+            limit: 1000,
+        };
+
+        let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+        // enough distinct colors, so every pixel is mapped on its own
+        let mut img = DynamicImage::from(RgbaImage::from_fn(500, 500, |x, y| {
+            rgba!(
+                u8::try_from(x % 256).unwrap(),
+                u8::try_from(y % 256).unwrap(),
+                0
+            )
+        }));
+
+        let result = map_image_to_palette_with(
+            &mut img,
+            &palette,
+            &algorithm,
+            &MapOptions::default().cancellation_token(token),
+        );
+
+        assert!(matches!(result, Err(Error::Cancelled)));
+        // every pixel takes two calculations, so finishing would take 500_000
+        assert!(algorithm.calls.load(Ordering::Relaxed) < 250_000);
+    }
+}
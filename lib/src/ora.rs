@@ -0,0 +1,199 @@
+//! Export of layered `OpenRaster` (`.ora`) files
+//!
+//! `OpenRaster` is the layered image format of GIMP, Krita and `MyPaint`. A `.ora` file is a zip
+//! archive containing every layer as a PNG, and a `stack.xml` describing their order:
+//!
+//! ```text
+//! mimetype                 "image/openraster", stored first and uncompressed
+//! stack.xml                the layer stack, from the top to the bottom layer
+//! data/layer0.png          one PNG per layer
+//! mergedimage.png          all layers composited
+//! Thumbnails/thumbnail.png the merged image, at most 256x256
+//! ```
+//!
+//! Saving the original and the mapped image as separate layers allows blending or toggling
+//! between them in an editor.
+use std::{
+    fmt::Write as _,
+    io::{Seek, Write},
+};
+
+use image::{DynamicImage, RgbaImage, imageops};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::{encoding, error::Error};
+
+/// Largest width and height of the thumbnail
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Write `layers` as an `OpenRaster` file
+///
+/// The layers are given as pairs of their name and image, starting with the top layer. All
+/// layers are placed at the top left corner, and the canvas is as large as the largest layer.
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{color_palette, distance::EuclideanDistance, mapped_to_palette, ora::write_ora};
+/// # use image::{DynamicImage, RgbaImage};
+/// # use std::io::Cursor;
+/// let original = DynamicImage::from(RgbaImage::new(4, 4));
+/// let mapped = mapped_to_palette(&original, &color_palette!([0, 0, 0]), &EuclideanDistance);
+///
+/// let mut file = Cursor::new(Vec::new());
+/// write_ora(&[("Mapped", &mapped), ("Original", &original)], &mut file)?;
+/// # Ok::<(), palette_mapper::Error>(())
+/// ```
+///
+/// ## Errors
+///
+/// [`Error::Io`] if encoding a layer or writing to `writer` fails.
+pub fn write_ora<W: Write + Seek>(
+    layers: &[(&str, &DynamicImage)],
+    writer: W,
+) -> Result<(), Error> {
+    let width = layers.iter().map(|(_, img)| img.width()).max().unwrap_or(0);
+    let height = layers
+        .iter()
+        .map(|(_, img)| img.height())
+        .max()
+        .unwrap_or(0);
+
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut archive = ZipWriter::new(writer);
+
+    archive
+        .start_file("mimetype", stored)
+        .map_err(std::io::Error::other)?;
+    archive.write_all(b"image/openraster")?;
+
+    archive
+        .start_file("stack.xml", SimpleFileOptions::default())
+        .map_err(std::io::Error::other)?;
+    archive.write_all(stack_xml(layers, width, height).as_bytes())?;
+
+    for (i, (_, img)) in layers.iter().enumerate() {
+        archive
+            .start_file(format!("data/layer{i}.png"), stored)
+            .map_err(std::io::Error::other)?;
+        encoding::write_png(img, &mut archive)?;
+    }
+
+    let mut merged = RgbaImage::new(width, height);
+    for (_, img) in layers.iter().rev() {
+        imageops::overlay(&mut merged, &img.to_rgba8(), 0, 0);
+    }
+    let merged = DynamicImage::from(merged);
+
+    archive
+        .start_file("mergedimage.png", stored)
+        .map_err(std::io::Error::other)?;
+    encoding::write_png(&merged, &mut archive)?;
+
+    // `thumbnail` would scale small images up
+    let thumbnail = if width > THUMBNAIL_SIZE || height > THUMBNAIL_SIZE {
+        merged.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    } else {
+        merged
+    };
+
+    archive
+        .start_file("Thumbnails/thumbnail.png", stored)
+        .map_err(std::io::Error::other)?;
+    encoding::write_png(&thumbnail, &mut archive)?;
+
+    archive.finish().map_err(std::io::Error::other)?;
+
+    Ok(())
+}
+
+/// The contents of `stack.xml` for `layers` on a canvas of `width` x `height`
+fn stack_xml(layers: &[(&str, &DynamicImage)], width: u32, height: u32) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<image version=\"0.0.5\" w=\"{width}\" h=\"{height}\">\n  <stack>\n"
+    );
+
+    for (i, (name, _)) in layers.iter().enumerate() {
+        let _ = writeln!(
+            xml,
+            "    <layer name=\"{}\" src=\"data/layer{i}.png\" x=\"0\" y=\"0\" opacity=\"1.0\" visibility=\"visible\"/>",
+            escape_xml(name)
+        );
+    }
+
+    xml.push_str("  </stack>\n</image>\n");
+
+    xml
+}
+
+/// Escape the characters which aren't allowed in an XML attribute value
+fn escape_xml(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut out, c| {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&apos;"),
+                c => out.push(c),
+            }
+
+            out
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_ora;
+    use crate::rgba;
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn ora_contains_layers() {
+        let original = DynamicImage::from(RgbaImage::from_pixel(6, 4, rgba!(200, 30, 30)));
+        let mapped = DynamicImage::from(RgbaImage::from_pixel(6, 4, rgba!(255, 0, 0)));
+
+        let mut buf = Cursor::new(Vec::new());
+        write_ora(
+            &[("Mapped", &mapped), ("Original & co", &original)],
+            &mut buf,
+        )
+        .unwrap();
+
+        let mut archive = ZipArchive::new(buf).unwrap();
+
+        // the mimetype must come first, so the file can be identified
+        assert_eq!(archive.by_index(0).unwrap().name().unwrap(), "mimetype");
+
+        let mut read = |name: &str| {
+            let mut contents = Vec::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut contents)
+                .unwrap();
+
+            contents
+        };
+
+        assert_eq!(read("mimetype"), b"image/openraster");
+
+        let stack = String::from_utf8(read("stack.xml")).unwrap();
+        assert!(stack.contains(r#"w="6" h="4""#));
+        assert!(stack.contains(r#"name="Mapped" src="data/layer0.png""#));
+        assert!(stack.contains(r#"name="Original &amp; co" src="data/layer1.png""#));
+
+        let png = |contents: Vec<u8>| {
+            image::load_from_memory_with_format(&contents, ImageFormat::Png).unwrap()
+        };
+
+        assert_eq!(png(read("data/layer0.png")), mapped);
+        assert_eq!(png(read("data/layer1.png")), original);
+        assert_eq!(png(read("mergedimage.png")), mapped);
+        assert_eq!(png(read("Thumbnails/thumbnail.png")).width(), 6);
+    }
+}
@@ -1,10 +1,27 @@
 //! Items relating to color Palettes
 //!
 //! The main type is [`Palette`].
-use image::Rgba;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
+use image::{Rgb, Rgba};
+
+use crate::{
+    conversions::{self, ConversionError, Hsv},
+    distance::DistanceAlgorithm,
+    error::Error,
+};
+
+pub mod format;
+#[cfg(feature = "procreate")]
+pub mod procreate;
+#[cfg(feature = "schema")]
+pub mod schema;
 #[cfg(feature = "serde")]
 mod serde;
+mod xresources;
 
 /// Helper macro for creating a [`Palette`]
 ///
@@ -76,12 +93,36 @@ macro_rules! rgba {
 /// intentionally not implemented, since doing so might lead to a breaking change latter down the
 /// road.
 ///
+/// ## Transforming
+///
+/// The methods modifying the palette in place return it again, so they can be chained:
+///
+/// ```
+/// # use palette_mapper::{color_palette, rgba};
+/// let mut p = color_palette!([255, 255, 255], [0, 0, 0], [255, 255, 255], [0, 0, 255]);
+///
+/// p.dedup().sort_by_luminance().invert();
+///
+/// assert_eq!(
+///     p,
+///     color_palette!([255, 255, 255], [255, 255, 0], [0, 0, 0])
+/// );
+/// ```
+///
+/// ## Thread safety
+///
+/// [`Palette`] is [`Send`] and [`Sync`], so a single palette can be shared between threads, e.g.
+/// behind an [`Arc`](std::sync::Arc), and used to map several images at once. This is checked at
+/// compile time and won't change.
+///
 /// ### **See also**
 ///
 /// - [`color_palette`]
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Palette(Vec<Rgba<u8>>);
 
+crate::assert_send_sync!(Palette, LuminanceCoefficients);
+
 impl Palette {
     /// Add a color to the palette
     pub fn add_color(&mut self, col: Rgba<u8>) {
@@ -109,6 +150,321 @@ impl Palette {
     pub const fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Returns true if all colors in the palette are fully opaque
+    ///
+    /// An empty palette is considered opaque.
+    #[must_use]
+    pub fn is_opaque(&self) -> bool {
+        self.iter().all(|c| c[3] == 255)
+    }
+
+    /// Find the closest color to `color`, only taking the red, green and blue channels into account
+    ///
+    /// The alpha values of both `color` and the palette are ignored. The returned color has the
+    /// palette color's red, green and blue values, and the alpha value of `color`.
+    ///
+    /// This is useful for mapping transparent images to opaque palettes, such as the base16
+    /// themes, without losing the transparency.
+    ///
+    /// Returns [`None`] if the palette is empty.
+    ///
+    /// ### **See also**
+    ///
+    /// - [`MapOptions::ignore_alpha`](crate::MapOptions::ignore_alpha)
+    #[must_use]
+    pub fn closest_color_rgb_ignoring_alpha<D: DistanceAlgorithm>(
+        &self,
+        color: &Rgba<u8>,
+        algorithm: &D,
+    ) -> Option<Rgba<u8>> {
+        let opaque = |c: &Rgba<u8>| Rgba::from([c[0], c[1], c[2], 255]);
+        let target = opaque(color);
+
+        self.iter()
+            .min_by_key(|pcolor| algorithm.distance(&target, &opaque(pcolor)))
+            .map(|pcolor| Rgba::from([pcolor[0], pcolor[1], pcolor[2], color[3]]))
+    }
+
+    /// Returns true if the palette only contains grays
+    ///
+    /// A color is considered gray if its red, green and blue values are equal. The alpha value is
+    /// not taken into account. An empty palette is considered grayscale.
+    #[must_use]
+    pub fn is_grayscale(&self) -> bool {
+        self.iter().all(|c| c[0] == c[1] && c[1] == c[2])
+    }
+
+    /// Sort the colors of the palette by the key returned by `f`
+    ///
+    /// The sort is stable, so colors with equal keys keep their order.
+    ///
+    /// ## Usage
+    ///
+    /// ```
+    /// # use palette_mapper::{color_palette, rgba};
+    /// let mut p = color_palette!([200, 0, 0], [0, 0, 0], [100, 50, 0]);
+    ///
+    /// // sort by the amount of red, descending
+    /// p.sort_by_key(|c| std::cmp::Reverse(c[0]));
+    ///
+    /// assert_eq!(p.iter().next(), Some(&rgba!(200, 0, 0)));
+    /// ```
+    pub fn sort_by_key<K: Ord, F: FnMut(&Rgba<u8>) -> K>(&mut self, f: F) -> &mut Self {
+        self.0.sort_by_key(f);
+
+        self
+    }
+
+    /// Sort the colors of the palette from dark to bright
+    ///
+    /// See [`Palette::luminance_range`] for how the luminance is determined.
+    pub fn sort_by_luminance(&mut self) -> &mut Self {
+        self.sort_by_luminance_with(LuminanceCoefficients::default())
+    }
+
+    /// Sort the colors of the palette from dark to bright, weighting the channels by
+    /// `coefficients`
+    ///
+    /// Colors with the same luminance keep their order.
+    pub fn sort_by_luminance_with(&mut self, coefficients: LuminanceCoefficients) -> &mut Self {
+        // The bits of non-negative floats are ordered the same as the floats themselves
+        self.sort_by_key(|c| coefficients.luminance(*c).max(0.0).to_bits())
+    }
+
+    /// Sort the colors of the palette by their hue, starting at red
+    ///
+    /// Grays have no hue, so they are sorted first, keeping their order.
+    pub fn sort_by_hue(&mut self) -> &mut Self {
+        // The bits of non-negative floats are ordered the same as the floats themselves
+        self.sort_by_key(|c| {
+            let gray = c[0] == c[1] && c[1] == c[2];
+
+            (
+                !gray,
+                Hsv::from(Rgb::from([c[0], c[1], c[2]])).0[0]
+                    .max(0.0)
+                    .to_bits(),
+            )
+        })
+    }
+
+    /// Keep only the colors for which `f` returns `true`
+    ///
+    /// The remaining colors keep their order.
+    ///
+    /// ## Usage
+    ///
+    /// ```
+    /// # use palette_mapper::{color_palette, rgba};
+    /// let mut p = color_palette!([0, 0, 0], [20, 10, 10], [200, 0, 0]);
+    ///
+    /// // drop the near-black colors
+    /// p.retain(|c| c.0[..3].iter().any(|&v| v > 32));
+    ///
+    /// assert_eq!(p, color_palette!([200, 0, 0]));
+    /// ```
+    pub fn retain<F: FnMut(&Rgba<u8>) -> bool>(&mut self, f: F) -> &mut Self {
+        self.0.retain(f);
+
+        self
+    }
+
+    /// Remove all but the first occurrence of every color
+    ///
+    /// Colors only differing in their alpha value are kept.
+    pub fn dedup(&mut self) -> &mut Self {
+        let mut seen = HashSet::with_capacity(self.len());
+
+        self.0.retain(|c| seen.insert(*c));
+
+        self
+    }
+
+    /// Remove all colors which are within `tolerance` of an earlier color that was kept
+    ///
+    /// Two colors are considered near if none of their channels (including alpha) differ by more
+    /// than `tolerance`, like with [`MapOptions::snap_tolerance`]. The colors are checked in order,
+    /// so of several near colors the first one is kept. Removed colors aren't compared against, so
+    /// in a chain of colors each near the previous one, every other color may be kept. A `tolerance` of `0` is the same as
+    /// [`Palette::dedup`].
+    ///
+    /// The kept colors are stored in a grid of cells `tolerance + 1` wide, so only the colors in
+    /// neighboring cells are compared. This keeps large generated or extracted palettes fast to
+    /// deduplicate, instead of comparing every pair of colors.
+    ///
+    /// [`MapOptions::snap_tolerance`]: crate::MapOptions::snap_tolerance
+    pub fn dedup_near(&mut self, tolerance: u8) -> &mut Self {
+        let cell_size = u16::from(tolerance) + 1;
+        let cell = |c: &Rgba<u8>| c.0.map(|v| u16::from(v) / cell_size);
+
+        // Every cell holds at most one kept color, as all colors within a cell are near each other
+        let mut kept = HashMap::<[u16; 4], Rgba<u8>>::with_capacity(self.len());
+
+        self.0.retain(|c| {
+            let [r, g, b, a] = cell(c);
+
+            let neighbors = (0..81).map(|i: u16| {
+                let offset = |channel: u32, v: u16| (v + i / 3_u16.pow(channel) % 3).checked_sub(1);
+
+                Some([offset(0, r)?, offset(1, g)?, offset(2, b)?, offset(3, a)?])
+            });
+
+            let near = neighbors.flatten().any(|neighbor| {
+                kept.get(&neighbor)
+                    .is_some_and(|k| k.0.iter().zip(c.0).all(|(k, v)| k.abs_diff(v) <= tolerance))
+            });
+
+            if !near {
+                kept.insert([r, g, b, a], *c);
+            }
+
+            !near
+        });
+
+        self
+    }
+
+    /// Invert the red, green and blue values of every color
+    ///
+    /// The alpha values are kept.
+    pub fn invert(&mut self) -> &mut Self {
+        for c in &mut self.0 {
+            for channel in &mut c.0[..3] {
+                *channel = u8::MAX - *channel;
+            }
+        }
+
+        self
+    }
+
+    /// Replace every color with the gray of the same luminance
+    ///
+    /// See [`Palette::luminance_range`] for how the luminance is determined. The alpha values are
+    /// kept.
+    pub fn grayscale(&mut self) -> &mut Self {
+        self.grayscale_with(LuminanceCoefficients::default())
+    }
+
+    /// Replace every color with the gray of the same luminance, weighting the channels by
+    /// `coefficients`
+    ///
+    /// The alpha values are kept.
+    pub fn grayscale_with(&mut self, coefficients: LuminanceCoefficients) -> &mut Self {
+        for c in &mut self.0 {
+            let luminance = coefficients.luminance(*c).clamp(0.0, 1.0);
+
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                reason = "The value is clamped to the range of u8 before casting."
+            )]
+            let gray = (conversions::srgb_encode(luminance) * 255.0)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+
+            *c = Rgba([gray, gray, gray, c[3]]);
+        }
+
+        self
+    }
+
+    /// Move every color towards `target`, including the alpha value
+    ///
+    /// An `amount` of `0.0` keeps the colors as they are, `1.0` replaces all of them with
+    /// `target`. Values outside of this range are clamped.
+    pub fn blend_towards(&mut self, target: Rgba<u8>, amount: f32) -> &mut Self {
+        let amount = amount.clamp(0.0, 1.0);
+
+        for c in &mut self.0 {
+            for (channel, target) in c.0.iter_mut().zip(target.0) {
+                #[allow(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_sign_loss,
+                    reason = "A blend of two u8 values fits in a u8."
+                )]
+                {
+                    *channel = (f32::from(target) - f32::from(*channel))
+                        .mul_add(amount, f32::from(*channel))
+                        .round() as u8;
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Rotate the hue of every color by `degrees`
+    ///
+    /// Saturation, brightness and the alpha values are kept. Grays have no hue, so they stay the
+    /// same.
+    pub fn rotate_hue(&mut self, degrees: f32) -> &mut Self {
+        for c in &mut self.0 {
+            let mut hsv = Hsv::from(Rgb::from([c[0], c[1], c[2]]));
+            hsv.0[0] = (hsv.0[0] + degrees).rem_euclid(360.0);
+
+            let Rgb([r, g, b]) = Rgb::from(hsv);
+
+            *c = Rgba([r, g, b, c[3]]);
+        }
+
+        self
+    }
+
+    /// Returns the mean of all colors in the palette, including their alpha values
+    ///
+    /// Each channel is averaged separately and rounded. Returns [`None`] if the palette is empty.
+    #[must_use]
+    pub fn average_color(&self) -> Option<Rgba<u8>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let len = self.len() as u64;
+
+        let sums = self.iter().fold([0_u64; 4], |mut sums, c| {
+            sums.iter_mut()
+                .zip(c.0)
+                .for_each(|(s, v)| *s += u64::from(v));
+
+            sums
+        });
+
+        #[allow(
+            clippy::cast_possible_truncation,
+            reason = "The mean of u8 values fits in a u8."
+        )]
+        Some(Rgba::from(sums.map(|sum| ((sum + len / 2) / len) as u8)))
+    }
+
+    /// Returns the lowest and highest relative luminance of the colors in the palette
+    ///
+    /// The luminance is in the range `0.0..=1.0`, where `0.0` is black and `1.0` is white. It is
+    /// the `Y` component of the XYZ color space, so it matches the perceived brightness of a
+    /// color. The alpha values are not taken into account.
+    ///
+    /// This uses the [Rec. 709](LuminanceCoefficients::Rec709) coefficients, see
+    /// [`Palette::luminance_range_with`] for using others.
+    ///
+    /// Returns [`None`] if the palette is empty.
+    #[must_use]
+    pub fn luminance_range(&self) -> Option<(f32, f32)> {
+        self.luminance_range_with(LuminanceCoefficients::default())
+    }
+
+    /// Returns the lowest and highest luminance of the colors in the palette, weighting the
+    /// channels by `coefficients`
+    ///
+    /// Returns [`None`] if the palette is empty.
+    #[must_use]
+    pub fn luminance_range_with(&self, coefficients: LuminanceCoefficients) -> Option<(f32, f32)> {
+        self.iter()
+            .map(|c| coefficients.luminance(*c))
+            .fold(None, |range, y| match range {
+                None => Some((y, y)),
+                Some((min, max)) => Some((y.min(min), y.max(max))),
+            })
+    }
 }
 
 impl<'a> IntoIterator for &'a Palette {
@@ -137,6 +493,134 @@ impl From<Vec<Rgba<u8>>> for Palette {
     }
 }
 
+impl TryFrom<Vec<Vec<u8>>> for Palette {
+    type Error = Error;
+
+    /// Create a palette from a list of colors given as `[r, g, b]` or `[r, g, b, a]`
+    fn try_from(value: Vec<Vec<u8>>) -> Result<Self, Self::Error> {
+        value
+            .into_iter()
+            .map(|channels| match *channels.as_slice() {
+                [r, g, b] => Ok(Rgba::from([r, g, b, 255])),
+                [r, g, b, alpha] => Ok(Rgba::from([r, g, b, alpha])),
+                [] | [_] | [_, _] => Err(ConversionError::InputTooShort.into()),
+                _ => Err(ConversionError::InputTooLong.into()),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
+impl FromStr for Palette {
+    type Err = Error;
+
+    /// Parse a list of hex colors separated by whitespace or commas
+    ///
+    /// Colors must be in the form `#RRGGBB` or `#RRGGBBAA`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|color| !color.is_empty())
+            .map(|color| {
+                parse_hex_color(color).map(Rgba::from).ok_or_else(|| {
+                    Error::InvalidPaletteData(format!(
+                        "invalid hex color `{color}` (expected #RRGGBB or #RRGGBBAA)"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
+/// Parses a hex color into an rgba array
+///
+/// Accepted forms:
+///
+/// ```no_test
+/// parse_hex_color("#ff00ff")
+/// parse_hex_color("#ff00ff00")
+/// ```
+fn parse_hex_color(s: &str) -> Option<[u8; 4]> {
+    let s = s.strip_prefix('#')?;
+    match s.len() {
+        6 => {
+            let r = u8::from_str_radix(&s[0..2], 16).ok()?;
+            let g = u8::from_str_radix(&s[2..4], 16).ok()?;
+            let b = u8::from_str_radix(&s[4..6], 16).ok()?;
+            Some([r, g, b, 255])
+        }
+        8 => {
+            let r = u8::from_str_radix(&s[0..2], 16).ok()?;
+            let g = u8::from_str_radix(&s[2..4], 16).ok()?;
+            let b = u8::from_str_radix(&s[4..6], 16).ok()?;
+            let alpha = u8::from_str_radix(&s[6..8], 16).ok()?;
+            Some([r, g, b, alpha])
+        }
+        _ => None,
+    }
+}
+
+/// Helper function to convert an rgba array into a hex string
+fn to_hex(rgba: [u8; 4]) -> String {
+    if rgba[3] == 255 {
+        format!("#{:02X}{:02X}{:02X}", rgba[0], rgba[1], rgba[2])
+    } else {
+        format!(
+            "#{:02X}{:02X}{:02X}{:02X}",
+            rgba[0], rgba[1], rgba[2], rgba[3]
+        )
+    }
+}
+
+/// The weights of the red, green and blue channels when computing the luminance of a color
+///
+/// Both sets are applied to linear (decoded) sRGB values, so the result is a relative luminance
+/// in `0.0..=1.0`. They differ in how bright saturated colors are considered, which affects e.g.
+/// [`Palette::sort_by_luminance_with`] and [`Palette::grayscale_with`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LuminanceCoefficients {
+    /// `0.2126`, `0.7152` and `0.0722`, as used by sRGB and HDTV
+    ///
+    /// These are the `Y` row of the sRGB to XYZ matrix, so this is the luminance of the XYZ color
+    /// space.
+    #[default]
+    Rec709,
+    /// `0.299`, `0.587` and `0.114`, as used by SDTV and JPEG
+    ///
+    /// Compared to [`LuminanceCoefficients::Rec709`], reds and blues are brighter and greens
+    /// darker.
+    ///
+    /// SDTV and JPEG apply these weights to the gamma-encoded values, computing luma. Here they
+    /// are applied to the linear values like the [`Rec709`](LuminanceCoefficients::Rec709)
+    /// weights, so both result in a relative luminance on the same scale. Grays therefore have
+    /// the same luminance with both sets, but the results differ from luma for other colors.
+    Rec601,
+}
+
+impl LuminanceCoefficients {
+    /// The weights of the red, green and blue channels
+    #[must_use]
+    pub const fn weights(self) -> [f32; 3] {
+        match self {
+            Self::Rec709 => conversions::D65_S_RGB_MATRIX[1],
+            Self::Rec601 => [0.299, 0.587, 0.114],
+        }
+    }
+
+    /// The relative luminance of `color`, in `0.0..=1.0`
+    ///
+    /// The alpha value is not taken into account.
+    #[must_use]
+    pub fn luminance(self, color: Rgba<u8>) -> f32 {
+        self.weights()
+            .iter()
+            .zip(&color.0[..3])
+            .fold(0.0, |total, (weight, &channel)| {
+                weight.mul_add(conversions::srgb_decode(f32::from(channel) / 255.0), total)
+            })
+    }
+}
+
 /// Immutable [Palette] iterator
 ///
 /// ## Note on implementation
@@ -172,3 +656,290 @@ impl Iterator for IntoIter {
         self.this.next()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{LuminanceCoefficients, Palette, parse_hex_color};
+    use crate::distance::{DistanceAlgorithm, EuclideanDistance};
+
+    #[test]
+    fn retain_opaque() {
+        let mut p = color_palette!(
+            [255, 0, 0],
+            [0, 255, 0, 128],
+            [0, 0, 255],
+            [0, 0, 0, 0],
+            [255, 255, 255, 254]
+        );
+
+        p.retain(|c| c[3] == 255);
+
+        assert_eq!(p, color_palette!([255, 0, 0], [0, 0, 255]));
+        assert!(p.is_opaque());
+    }
+
+    #[test]
+    fn dedup_near_chain() {
+        // the middle color is near both others, which aren't near each other
+        let mut p = color_palette!([100, 100, 100], [105, 100, 100], [110, 100, 100]);
+        p.dedup_near(5);
+
+        assert_eq!(p, color_palette!([100, 100, 100], [110, 100, 100]));
+    }
+
+    #[test]
+    fn dedup_near_scales() {
+        // 10240 colors, each differing from its neighbors by 1 in red or green
+        let colors: Vec<_> = (0..10_240_u32)
+            .map(|i| {
+                let [r, g, ..] = i.to_le_bytes();
+                rgba!(r, g, 0)
+            })
+            .collect();
+
+        let mut p = Palette::from(colors);
+
+        p.dedup_near(1);
+
+        // every other red and green value remains, the timing is covered by the benchmark
+        assert_eq!(p.len(), 128 * 20);
+        assert!(p.iter().all(|c| c[0] % 2 == 0 && c[1] % 2 == 0));
+
+        let mut p = color_palette!([10, 10, 10], [13, 10, 10], [14, 11, 10], [10, 10, 10, 252]);
+        p.dedup_near(3);
+        assert_eq!(p, color_palette!([10, 10, 10], [14, 11, 10]));
+
+        let mut p = color_palette!([10, 10, 10], [11, 10, 10], [10, 10, 10]);
+        p.dedup_near(0);
+        assert_eq!(p, color_palette!([10, 10, 10], [11, 10, 10]));
+    }
+
+    #[test]
+    fn share_between_threads() {
+        let palette = &color_palette!([0, 0, 0], [255, 255, 255]);
+
+        let closest = std::thread::scope(|scope| {
+            [0, 60, 120, 180]
+                .map(|v| {
+                    scope.spawn(move || {
+                        *crate::closest_color_in_palette(
+                            &rgba!(v, v, v),
+                            palette,
+                            &EuclideanDistance,
+                        )
+                        .unwrap()
+                    })
+                })
+                .map(|handle| handle.join().unwrap())
+        });
+
+        assert_eq!(
+            closest,
+            [
+                rgba!(0, 0, 0),
+                rgba!(0, 0, 0),
+                rgba!(0, 0, 0),
+                rgba!(255, 255, 255)
+            ]
+        );
+    }
+
+    #[test]
+    fn in_place_transforms() {
+        let mut p = color_palette!([255, 0, 0], [0, 0, 0, 100], [255, 0, 0], [255, 0, 0, 0]);
+
+        p.dedup();
+        assert_eq!(
+            p,
+            color_palette!([255, 0, 0], [0, 0, 0, 100], [255, 0, 0, 0])
+        );
+
+        p.invert();
+        assert_eq!(
+            p,
+            color_palette!([0, 255, 255], [255, 255, 255, 100], [0, 255, 255, 0])
+        );
+
+        p.rotate_hue(180.0);
+        assert_eq!(
+            p,
+            color_palette!([255, 0, 0], [255, 255, 255, 100], [255, 0, 0, 0])
+        );
+
+        p.blend_towards(rgba!(0, 0, 0, 255), 0.5);
+        assert_eq!(
+            p,
+            color_palette!([128, 0, 0, 255], [128, 128, 128, 178], [128, 0, 0, 128])
+        );
+
+        p.grayscale();
+        assert!(p.is_grayscale());
+        // alpha values are kept
+        assert_eq!(p.iter().map(|c| c[3]).collect::<Vec<_>>(), [255, 178, 128]);
+
+        // all transforms can be chained
+        let mut chained = color_palette!([0, 0, 255], [255, 255, 255], [0, 0, 255]);
+        chained
+            .dedup()
+            .rotate_hue(120.0)
+            .sort_by_hue()
+            .blend_towards(rgba!(0, 0, 0), 0.0)
+            .invert()
+            .grayscale()
+            .sort_by_luminance();
+
+        assert_eq!(chained, color_palette!([0, 0, 0], [229, 229, 229]));
+    }
+
+    #[test]
+    fn parse_hex_colors() {
+        assert_eq!(parse_hex_color("#ff00ff"), Some([255, 0, 255, 255]));
+        assert_eq!(parse_hex_color("#ff00ff00"), Some([255, 0, 255, 0]));
+    }
+
+    #[test]
+    fn closest_color_ignoring_alpha() {
+        let palette = color_palette!([0, 0, 0, 0], [255, 0, 0], [0, 0, 255, 10]);
+
+        assert_eq!(
+            palette.closest_color_rgb_ignoring_alpha(&rgba!(250, 10, 5, 100), &EuclideanDistance),
+            Some(rgba!(255, 0, 0, 100))
+        );
+        assert_eq!(
+            palette.closest_color_rgb_ignoring_alpha(&rgba!(10, 10, 5, 255), &EuclideanDistance),
+            Some(rgba!(0, 0, 0, 255))
+        );
+        assert_eq!(
+            palette.closest_color_rgb_ignoring_alpha(&rgba!(0, 0, 200, 0), &EuclideanDistance),
+            Some(rgba!(0, 0, 255, 0))
+        );
+        assert_eq!(
+            Palette::default()
+                .closest_color_rgb_ignoring_alpha(&rgba!(0, 0, 0), &EuclideanDistance),
+            None
+        );
+    }
+
+    #[test]
+    fn average_color() {
+        let palette = color_palette!([0, 0, 0], [255, 255, 255]);
+
+        assert_eq!(palette.average_color(), Some(rgba!(128, 128, 128)));
+        assert_eq!(
+            color_palette!([10, 0, 0, 0], [20, 0, 0, 100], [0, 0, 30, 255]).average_color(),
+            Some(rgba!(10, 0, 10, 118))
+        );
+        assert_eq!(Palette::default().average_color(), None);
+    }
+
+    #[test]
+    fn luminance_range() {
+        let (min, max) = color_palette!([255, 255, 255], [0, 0, 0], [255, 0, 0])
+            .luminance_range()
+            .unwrap();
+
+        assert!(min.abs() < 1e-4, "{min}");
+        assert!((max - 1.0).abs() < 1e-3, "{max}");
+
+        assert_eq!(Palette::default().luminance_range(), None);
+    }
+
+    #[test]
+    fn sort_by_distance_from_red() {
+        let mut palette = color_palette!(
+            [0, 0, 255],
+            [200, 30, 30],
+            [0, 255, 0],
+            [255, 0, 0],
+            [128, 128, 128]
+        );
+
+        palette.sort_by_key(|c| EuclideanDistance.distance(c, &rgba!(255, 0, 0)));
+
+        assert_eq!(
+            palette,
+            color_palette!(
+                [255, 0, 0],
+                [200, 30, 30],
+                [128, 128, 128],
+                [0, 0, 255],
+                [0, 255, 0]
+            )
+        );
+    }
+
+    #[test]
+    fn luminance_coefficients_rank_red_and_green_differently() {
+        let red = rgba!(255, 0, 0);
+        let green = rgba!(0, 170, 0);
+
+        let mut p = color_palette!([255, 0, 0], [0, 170, 0]);
+
+        p.sort_by_luminance_with(LuminanceCoefficients::Rec709);
+        assert_eq!(p, color_palette!([255, 0, 0], [0, 170, 0]));
+
+        p.sort_by_luminance_with(LuminanceCoefficients::Rec601);
+        assert_eq!(p, color_palette!([0, 170, 0], [255, 0, 0]));
+
+        assert!(
+            LuminanceCoefficients::Rec709.luminance(red)
+                < LuminanceCoefficients::Rec709.luminance(green)
+        );
+        assert!(
+            LuminanceCoefficients::Rec601.luminance(red)
+                > LuminanceCoefficients::Rec601.luminance(green)
+        );
+
+        // the weights of both sets add up to white
+        for coefficients in [LuminanceCoefficients::Rec709, LuminanceCoefficients::Rec601] {
+            assert!((coefficients.luminance(rgba!(255, 255, 255)) - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sort_by_luminance_and_hue() {
+        let mut palette = color_palette!([255, 255, 255], [0, 0, 255], [0, 0, 0], [0, 255, 0]);
+
+        palette.sort_by_luminance();
+        assert_eq!(
+            palette,
+            color_palette!([0, 0, 0], [0, 0, 255], [0, 255, 0], [255, 255, 255])
+        );
+
+        let mut palette = color_palette!(
+            [0, 0, 255],
+            [0, 255, 0],
+            [255, 0, 0],
+            [128, 128, 128],
+            [0, 0, 0]
+        );
+
+        palette.sort_by_hue();
+        assert_eq!(
+            palette,
+            color_palette!(
+                [128, 128, 128],
+                [0, 0, 0],
+                [255, 0, 0],
+                [0, 255, 0],
+                [0, 0, 255]
+            )
+        );
+    }
+
+    #[test]
+    fn palette_from_str() {
+        assert_eq!(
+            "#ff00ff, #0C2D43C8\n#57d42d".parse::<Palette>().unwrap(),
+            color_palette!([255, 0, 255], [12, 45, 67, 200], [87, 212, 45])
+        );
+    }
+
+    #[test]
+    fn palette_try_from_channels() {
+        assert_eq!(
+            Palette::try_from(vec![vec![1, 2, 3], vec![4, 5, 6, 7]]).unwrap(),
+            color_palette!([1, 2, 3], [4, 5, 6, 7])
+        );
+    }
+}
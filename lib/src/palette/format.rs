@@ -0,0 +1,303 @@
+//! Reading and writing palette files in different formats
+//!
+//! Every file format is a type implementing [`PaletteFormat`]. A [`FormatRegistry`] maps file
+//! extensions to formats, so a palette file can be read or written based on its path. Custom
+//! formats can be added by implementing the trait and [registering](FormatRegistry::register)
+//! them.
+//!
+//! The formats built into the [`Default`] registry are:
+//!
+//! | Extension  | Format       | Feature     |
+//! |------------|--------------|-------------|
+//! | `txt`      | [`LineWise`] |             |
+//! | `json`     | `Json`       | `serde`     |
+//! | `swatches` | `Swatches`   | `procreate` |
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use super::{Palette, to_hex};
+use crate::error::Error;
+
+/// A file format palettes can be read from and written to
+///
+/// Formats are used through `dyn` references, so the readers and writers are trait objects as
+/// well. Formats which need to seek can buffer the whole file in memory.
+pub trait PaletteFormat: Send + Sync {
+    /// Read a palette from `reader`
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::InvalidPaletteData`] if the contents aren't a valid palette in this format, or
+    /// [`Error::Io`] if reading fails.
+    fn read(&self, reader: &mut dyn Read) -> Result<Palette, Error>;
+
+    /// Write `palette` to `writer`
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::Io`] if writing fails.
+    fn write(&self, palette: &Palette, writer: &mut dyn Write) -> Result<(), Error>;
+}
+
+/// Hex colors separated by whitespace or commas, as parsed by [`Palette::from_str`]
+///
+/// Palettes are written with one color per line.
+///
+/// [`Palette::from_str`]: std::str::FromStr::from_str
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LineWise;
+
+impl PaletteFormat for LineWise {
+    fn read(&self, reader: &mut dyn Read) -> Result<Palette, Error> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+
+        contents.parse()
+    }
+
+    fn write(&self, palette: &Palette, writer: &mut dyn Write) -> Result<(), Error> {
+        for color in palette {
+            writeln!(writer, "{}", to_hex(color.0))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A JSON array of colors, as hex strings or arrays of channels
+///
+/// See the [`Deserialize`](::serde::Deserialize) implementation of [`Palette`].
+#[cfg(feature = "serde")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Json;
+
+#[cfg(feature = "serde")]
+impl PaletteFormat for Json {
+    fn read(&self, reader: &mut dyn Read) -> Result<Palette, Error> {
+        serde_json::from_reader(std::io::BufReader::new(reader))
+            .map_err(|e| Error::InvalidPaletteData(e.to_string()))
+    }
+
+    fn write(&self, palette: &Palette, writer: &mut dyn Write) -> Result<(), Error> {
+        serde_json::to_writer_pretty(writer, palette).map_err(std::io::Error::other)?;
+
+        Ok(())
+    }
+}
+
+/// A Procreate `.swatches` file
+///
+/// See [`read_swatches`](super::procreate::read_swatches). The palette is named "Palette" when
+/// written.
+#[cfg(feature = "procreate")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Swatches;
+
+#[cfg(feature = "procreate")]
+impl PaletteFormat for Swatches {
+    fn read(&self, reader: &mut dyn Read) -> Result<Palette, Error> {
+        // zip archives have to be read from the end
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+
+        super::procreate::read_swatches(std::io::Cursor::new(contents))
+    }
+
+    fn write(&self, palette: &Palette, writer: &mut dyn Write) -> Result<(), Error> {
+        let mut contents = std::io::Cursor::new(Vec::new());
+        super::procreate::write_swatches(palette, "Palette", &mut contents)?;
+
+        writer.write_all(contents.get_ref())?;
+
+        Ok(())
+    }
+}
+
+/// A mapping from file extensions to [`PaletteFormat`]s
+///
+/// Extensions are given without the leading dot and are case-insensitive.
+///
+/// ## Usage
+///
+/// ```
+/// # use palette_mapper::{color_palette, palette::format::{FormatRegistry, LineWise}};
+/// let mut registry = FormatRegistry::default();
+/// registry.register("hex", LineWise);
+///
+/// let mut file = Vec::new();
+/// registry
+///     .get("HEX")
+///     .unwrap()
+///     .write(&color_palette!([255, 0, 0]), &mut file)?;
+///
+/// assert_eq!(file, b"#FF0000\n");
+/// # Ok::<(), palette_mapper::Error>(())
+/// ```
+pub struct FormatRegistry {
+    /// The formats by their lowercase extension
+    formats: HashMap<String, Box<dyn PaletteFormat>>,
+}
+
+impl FormatRegistry {
+    /// Create a registry without any formats
+    ///
+    /// Use [`FormatRegistry::default`] for one containing the built-in formats.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            formats: HashMap::new(),
+        }
+    }
+
+    /// Use `format` for files ending in `extension`
+    ///
+    /// Replaces the format previously registered for `extension`, if any.
+    pub fn register(&mut self, extension: &str, format: impl PaletteFormat + 'static) -> &mut Self {
+        self.formats
+            .insert(extension.to_lowercase(), Box::new(format));
+
+        self
+    }
+
+    /// The format registered for `extension`
+    #[must_use]
+    pub fn get(&self, extension: &str) -> Option<&dyn PaletteFormat> {
+        self.formats
+            .get(&extension.to_lowercase())
+            .map(AsRef::as_ref)
+    }
+
+    /// All registered extensions, sorted alphabetically
+    #[must_use]
+    pub fn extensions(&self) -> Vec<&str> {
+        let mut extensions: Vec<_> = self.formats.keys().map(String::as_str).collect();
+        extensions.sort_unstable();
+
+        extensions
+    }
+}
+
+impl Default for FormatRegistry {
+    /// A registry containing all built-in formats enabled by the crate's features
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register("txt", LineWise);
+
+        #[cfg(feature = "serde")]
+        registry.register("json", Json);
+
+        #[cfg(feature = "procreate")]
+        registry.register("swatches", Swatches);
+
+        registry
+    }
+}
+
+impl std::fmt::Debug for FormatRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FormatRegistry")
+            .field("extensions", &self.extensions())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FormatRegistry, PaletteFormat};
+    use crate::{Error, Palette, color_palette};
+    use std::io::{Read, Write};
+
+    /// Colors as `r g b a` lines of decimal numbers
+    struct Decimal;
+
+    impl PaletteFormat for Decimal {
+        fn read(&self, reader: &mut dyn Read) -> Result<Palette, Error> {
+            let mut contents = String::new();
+            reader.read_to_string(&mut contents)?;
+
+            contents
+                .lines()
+                .map(|line| {
+                    let channels: Vec<u8> = line
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|e: std::num::ParseIntError| {
+                            Error::InvalidPaletteData(e.to_string())
+                        })?;
+
+                    <[u8; 4]>::try_from(channels)
+                        .map(image::Rgba)
+                        .map_err(|_| Error::InvalidPaletteData(line.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Palette::from)
+        }
+
+        fn write(&self, palette: &Palette, writer: &mut dyn Write) -> Result<(), Error> {
+            for [r, g, b, a] in palette.iter().map(|c| c.0) {
+                writeln!(writer, "{r} {g} {b} {a}")?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Write `palette` in the format registered for `extension` and read it back
+    fn round_trip(registry: &FormatRegistry, extension: &str, palette: &Palette) -> Palette {
+        let format = registry.get(extension).unwrap();
+
+        let mut file = Vec::new();
+        format.write(palette, &mut file).unwrap();
+
+        format.read(&mut file.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn custom_format_round_trip() {
+        let palette = color_palette!([255, 0, 0], [0, 128, 255, 64], [1, 2, 3]);
+
+        let mut registry = FormatRegistry::empty();
+        assert!(registry.get("dec").is_none());
+
+        registry.register("dec", Decimal);
+
+        assert_eq!(registry.extensions(), ["dec"]);
+        assert_eq!(round_trip(&registry, "DEC", &palette), palette);
+
+        let mut file = Vec::new();
+        registry
+            .get("dec")
+            .unwrap()
+            .write(&palette, &mut file)
+            .unwrap();
+        assert_eq!(file, b"255 0 0 255\n0 128 255 64\n1 2 3 255\n");
+
+        assert!(matches!(
+            registry.get("dec").unwrap().read(&mut b"1 2 3".as_slice()),
+            Err(Error::InvalidPaletteData(_))
+        ));
+
+        // the built-in formats can be replaced
+        let mut registry = FormatRegistry::default();
+        registry.register("txt", Decimal);
+        assert_eq!(round_trip(&registry, "txt", &palette), palette);
+    }
+
+    #[test]
+    fn builtin_formats_round_trip() {
+        let palette = color_palette!([255, 0, 0], [0, 128, 255, 64], [1, 2, 3]);
+        let registry = FormatRegistry::default();
+
+        for extension in registry.extensions() {
+            assert_eq!(
+                round_trip(&registry, extension, &palette),
+                palette,
+                "{extension}"
+            );
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Import and export of Procreate `.swatches` files
+//!
+//! A `.swatches` file is a zip archive containing a single `Swatches.json`. The colors in it are
+//! stored as HSV values, where every component (including the hue) is in the range `0..=1`.
+//!
+//! ```json
+//! [{
+//!     "name": "My palette",
+//!     "swatches": [
+//!         { "hue": 0.0, "saturation": 1.0, "brightness": 1.0, "alpha": 1.0, "colorSpace": 0 },
+//!         null
+//!     ]
+//! }]
+//! ```
+//!
+//! Empty slots in the palette are stored as `null` and skipped when importing.
+use std::io::{Read, Seek, Write};
+
+use image::{Rgb, Rgba};
+use serde::{Deserialize, Serialize};
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use super::Palette;
+use crate::{conversions::Hsv, error::Error};
+
+/// Name of the json file inside the archive
+const SWATCHES_JSON: &str = "Swatches.json";
+
+/// The contents of `Swatches.json`
+///
+/// Procreate writes a list with a single palette, but some tools write the palette on its own.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SwatchesFile {
+    /// A list of palettes, of which only the first one is used
+    List(Vec<SwatchesPalette>),
+    /// A single palette
+    Single(SwatchesPalette),
+}
+
+/// A single palette in `Swatches.json`
+#[derive(Deserialize, Serialize)]
+struct SwatchesPalette {
+    /// Name of the palette as displayed in Procreate
+    name: String,
+    /// The colors, with `None` for empty slots
+    swatches: Vec<Option<Swatch>>,
+}
+
+/// A single color in `Swatches.json`
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Swatch {
+    /// Hue in the range `0..=1`
+    hue: f32,
+    /// Saturation in the range `0..=1`
+    saturation: f32,
+    /// Brightness (value) in the range `0..=1`
+    brightness: f32,
+    /// Alpha in the range `0..=1`
+    alpha: f32,
+    /// The color space, `0` being HSB
+    #[serde(default)]
+    color_space: u8,
+}
+
+impl Swatch {
+    /// Convert the swatch to an [`Rgba`], ensuring all values are in range
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "The alpha is validated to be in 0..=1 before casting."
+    )]
+    fn to_rgba(&self) -> Result<Rgba<u8>, Error> {
+        let components = [self.hue, self.saturation, self.brightness, self.alpha];
+
+        if components.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return Err(Error::InvalidPaletteData(
+                "swatch values must be in the range 0..=1".to_string(),
+            ));
+        }
+
+        let rgb = Rgb::<u8>::from(Hsv::from([
+            self.hue * 360.0,
+            self.saturation,
+            self.brightness,
+        ]));
+
+        Ok(Rgba::from([
+            rgb[0],
+            rgb[1],
+            rgb[2],
+            (self.alpha * 255.0).round() as u8,
+        ]))
+    }
+}
+
+impl From<&Rgba<u8>> for Swatch {
+    fn from(value: &Rgba<u8>) -> Self {
+        let [hue, saturation, brightness] = Hsv::from(Rgb::from([value[0], value[1], value[2]])).0;
+
+        Self {
+            hue: hue / 360.0,
+            saturation,
+            brightness,
+            alpha: f32::from(value[3]) / 255.0,
+            color_space: 0,
+        }
+    }
+}
+
+/// Read a palette from a Procreate `.swatches` file
+///
+/// ## Errors
+///
+/// [`Error::InvalidPaletteData`] if `reader` isn't a zip archive containing a valid
+/// `Swatches.json`.
+pub fn read_swatches<R: Read + Seek>(reader: R) -> Result<Palette, Error> {
+    let invalid = |e: &dyn std::fmt::Display| Error::InvalidPaletteData(e.to_string());
+
+    let mut archive = ZipArchive::new(reader).map_err(|e| invalid(&e))?;
+
+    let file = archive.by_name(SWATCHES_JSON).map_err(|e| invalid(&e))?;
+
+    let palette = match serde_json::from_reader(file).map_err(|e| invalid(&e))? {
+        SwatchesFile::List(list) => list
+            .into_iter()
+            .next()
+            .ok_or_else(|| invalid(&"Swatches.json doesn't contain a palette"))?,
+        SwatchesFile::Single(palette) => palette,
+    };
+
+    palette
+        .swatches
+        .iter()
+        .flatten()
+        .map(Swatch::to_rgba)
+        .collect::<Result<Vec<_>, _>>()
+        .map(Palette::from)
+}
+
+/// Write a palette as a Procreate `.swatches` file
+///
+/// `name` is the name of the palette shown in Procreate. Note that Procreate only displays the
+/// first 30 colors of a palette.
+///
+/// ## Errors
+///
+/// [`Error::Io`] if writing to `writer` fails.
+pub fn write_swatches<W: Write + Seek>(
+    palette: &Palette,
+    name: &str,
+    writer: W,
+) -> Result<(), Error> {
+    let mut archive = ZipWriter::new(writer);
+
+    archive
+        .start_file(SWATCHES_JSON, SimpleFileOptions::default())
+        .map_err(std::io::Error::other)?;
+
+    let contents = [SwatchesPalette {
+        name: name.to_string(),
+        swatches: palette.iter().map(|c| Some(Swatch::from(c))).collect(),
+    }];
+
+    serde_json::to_writer(&mut archive, &contents).map_err(std::io::Error::other)?;
+
+    archive.finish().map_err(std::io::Error::other)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_swatches, write_swatches};
+    use crate::{Error, color_palette};
+    use std::io::{Cursor, Write};
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    #[test]
+    fn swatches_roundtrip() {
+        let palette = color_palette!([255, 0, 0], [12, 45, 67, 200], [87, 212, 45], [0, 0, 0]);
+
+        let mut buf = Cursor::new(Vec::new());
+        write_swatches(&palette, "Test", &mut buf).unwrap();
+
+        buf.set_position(0);
+
+        assert_eq!(read_swatches(buf).unwrap(), palette);
+    }
+
+    #[test]
+    fn swatches_invalid_json() {
+        let mut buf = Cursor::new(Vec::new());
+
+        let mut archive = ZipWriter::new(&mut buf);
+        archive
+            .start_file("Swatches.json", SimpleFileOptions::default())
+            .unwrap();
+        archive
+            .write_all(br#"[{"name": "Test", "swatches": [{"hue": 2.0, "saturation": 0.5, "brightness": 0.5, "alpha": 1}]}]"#)
+            .unwrap();
+        archive.finish().unwrap();
+
+        buf.set_position(0);
+
+        assert!(matches!(
+            read_swatches(buf),
+            Err(Error::InvalidPaletteData(_))
+        ));
+
+        assert!(matches!(
+            read_swatches(Cursor::new(b"not a zip")),
+            Err(Error::InvalidPaletteData(_))
+        ));
+    }
+}
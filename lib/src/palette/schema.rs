@@ -0,0 +1,86 @@
+//! JSON Schema for the serialized form of [`Palette`]
+//!
+//! This allows editors and validators to assist with writing palette files.
+use std::borrow::Cow;
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema, schema_for};
+
+use super::Palette;
+
+impl JsonSchema for Palette {
+    fn schema_name() -> Cow<'static, str> {
+        "Palette".into()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        concat!(module_path!(), "::Palette").into()
+    }
+
+    /// A list of colors, each either a hex string or an array of `[r, g, b]` or `[r, g, b, a]`
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A color palette",
+            "type": "array",
+            "items": {
+                "anyOf": [
+                    {
+                        "description": "A hex color: #RRGGBB or #RRGGBBAA",
+                        "type": "string",
+                        "pattern": "^#([0-9a-fA-F]{6}|[0-9a-fA-F]{8})$"
+                    },
+                    {
+                        "description": "A color as [r, g, b] or [r, g, b, a]",
+                        "type": "array",
+                        "items": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "maximum": 255
+                        },
+                        "minItems": 3,
+                        "maxItems": 4
+                    }
+                ]
+            }
+        })
+    }
+}
+
+/// Returns the JSON Schema for palette files
+///
+/// ## Usage
+///
+/// ```
+/// let schema = palette_mapper::palette::schema::schema();
+///
+/// println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+/// ```
+#[must_use]
+pub fn schema() -> Schema {
+    schema_for!(Palette)
+}
+
+#[cfg(test)]
+mod test {
+    use super::schema;
+    use serde_json::json;
+
+    #[test]
+    fn schema_validates_palettes() {
+        let schema = serde_json::to_value(schema()).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        assert!(validator.is_valid(&json!([
+            "#0C2D43C8",
+            "#57d42d",
+            [87, 212, 45],
+            [1, 2, 3, 4]
+        ])));
+        assert!(validator.is_valid(&json!([])));
+
+        assert!(!validator.is_valid(&json!(["0C2D43"])));
+        assert!(!validator.is_valid(&json!(["#0C2D4"])));
+        assert!(!validator.is_valid(&json!([[87, 212]])));
+        assert!(!validator.is_valid(&json!([[87, 212, 256]])));
+        assert!(!validator.is_valid(&json!({"colors": ["#57d42d"]})));
+    }
+}
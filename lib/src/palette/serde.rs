@@ -1,5 +1,5 @@
 //! Module for serde-related items for [`Palette`]
-use super::Palette;
+use super::{Palette, parse_hex_color, to_hex};
 use image::Rgba;
 
 use serde::{
@@ -18,46 +18,6 @@ enum ColorRepr {
     Array(Vec<u8>),
 }
 
-/// Parses a hex color into an rgba array
-///
-/// Accepted forms:
-///
-/// ```no_test
-/// parse_hex_color("#ff00ff")
-/// parse_hex_color("#ff00ff00")
-/// ```
-fn parse_hex_color(s: &str) -> Option<[u8; 4]> {
-    let s = s.strip_prefix('#')?;
-    match s.len() {
-        6 => {
-            let r = u8::from_str_radix(&s[0..2], 16).ok()?;
-            let g = u8::from_str_radix(&s[2..4], 16).ok()?;
-            let b = u8::from_str_radix(&s[4..6], 16).ok()?;
-            Some([r, g, b, 255])
-        }
-        8 => {
-            let r = u8::from_str_radix(&s[0..2], 16).ok()?;
-            let g = u8::from_str_radix(&s[2..4], 16).ok()?;
-            let b = u8::from_str_radix(&s[4..6], 16).ok()?;
-            let alpha = u8::from_str_radix(&s[6..8], 16).ok()?;
-            Some([r, g, b, alpha])
-        }
-        _ => None,
-    }
-}
-
-/// Helper function to convert an rgba array into a hex string
-fn to_hex(rgba: [u8; 4]) -> String {
-    if rgba[3] == 255 {
-        format!("#{:02X}{:02X}{:02X}", rgba[0], rgba[1], rgba[2])
-    } else {
-        format!(
-            "#{:02X}{:02X}{:02X}{:02X}",
-            rgba[0], rgba[1], rgba[2], rgba[3]
-        )
-    }
-}
-
 impl Serialize for Palette {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -107,15 +67,8 @@ impl<'de> Deserialize<'de> for Palette {
 
 #[cfg(test)]
 mod test {
-    use super::parse_hex_color;
     use crate::color_palette;
 
-    #[test]
-    fn parse_hex_colors() {
-        assert_eq!(parse_hex_color("#ff00ff"), Some([255, 0, 255, 255]));
-        assert_eq!(parse_hex_color("#ff00ff00"), Some([255, 0, 255, 0]));
-    }
-
     #[test]
     fn palette_serde_roundtrip() {
         let p = color_palette!([12, 45, 67, 200], [87, 212, 45]);
@@ -0,0 +1,180 @@
+//! Import of terminal color schemes from Xresources
+//!
+//! Terminal themes are commonly distributed as an Xresources block defining the 16 (or more)
+//! terminal colors:
+//!
+//! ```text
+//! ! comments start with an exclamation mark
+//! #define red #cc241d
+//!
+//! *.color0: #282828
+//! *.color1: red
+//! URxvt.color2: rgb:98/97/1a
+//! ```
+use std::collections::{BTreeMap, HashMap};
+
+use image::Rgba;
+
+use super::{Palette, parse_hex_color};
+use crate::error::Error;
+
+impl Palette {
+    /// Parse the terminal colors from an Xresources block
+    ///
+    /// Every resource whose name ends in `colorN` (such as `*.color0`, `*color0` or
+    /// `URxvt.color12`) is read, all other resources (like `*.foreground`) are ignored. The colors
+    /// are ordered by their number `N`, and if a color is defined more than once, the last
+    /// definition is used.
+    ///
+    /// Colors may be given as `#RRGGBB` or in the X11 `rgb:RR/GG/BB` notation, where each channel
+    /// has one to four hex digits. Simple `#define NAME VALUE` macros are substituted, other
+    /// preprocessor directives and `!` comments are skipped.
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::InvalidPaletteData`] if the value of a `colorN` resource isn't a valid color.
+    ///
+    /// ## Usage
+    ///
+    /// ```
+    /// # use palette_mapper::{Palette, color_palette};
+    /// let palette = Palette::from_xresources(
+    ///     "*.color1: #ff0000
+    ///      *.color0: rgb:00/00/00",
+    /// )?;
+    ///
+    /// assert_eq!(palette, color_palette!([0, 0, 0], [255, 0, 0]));
+    /// # Ok::<(), palette_mapper::Error>(())
+    /// ```
+    pub fn from_xresources(text: &str) -> Result<Self, Error> {
+        let mut defines = HashMap::new();
+        let mut colors = BTreeMap::new();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(directive) = line.strip_prefix('#') {
+                if let Some((name, value)) = directive
+                    .strip_prefix("define")
+                    .and_then(|define| define.trim().split_once(char::is_whitespace))
+                {
+                    defines.insert(name, value.trim());
+                }
+
+                continue;
+            }
+
+            if line.starts_with('!') {
+                continue;
+            }
+
+            let Some((resource, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let Some(index) = color_index(resource.trim()) else {
+                continue;
+            };
+
+            let value = value.trim();
+            let value = defines.get(value).copied().unwrap_or(value);
+
+            let color = parse_hex_color(value)
+                .or_else(|| parse_x11_rgb(value))
+                .ok_or_else(|| {
+                    Error::InvalidPaletteData(format!(
+                        "invalid color `{value}` for `{}` (expected #RRGGBB or rgb:RR/GG/BB)",
+                        resource.trim()
+                    ))
+                })?;
+
+            colors.insert(index, Rgba::from(color));
+        }
+
+        Ok(Self(colors.into_values().collect()))
+    }
+}
+
+/// The number `N` of a resource named `...colorN`, or [`None`] for all other resources
+fn color_index(resource: &str) -> Option<u32> {
+    let name = resource.rsplit(['.', '*']).next()?;
+
+    name.strip_prefix("color")
+        .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))?
+        .parse()
+        .ok()
+}
+
+/// Parses a color in the X11 `rgb:R/G/B` notation, where each channel has one to four hex digits
+///
+/// The channels are scaled from their number of digits to `0..=255`, so `rgb:f/8/0` is the same
+/// as `rgb:ff/88/00`.
+fn parse_x11_rgb(s: &str) -> Option<[u8; 4]> {
+    let mut channels = s.strip_prefix("rgb:")?.split('/').map(|channel| {
+        if !(1..=4).contains(&channel.len()) {
+            return None;
+        }
+
+        let value = u32::from_str_radix(channel, 16).ok()?;
+        let max = (1_u32 << (4 * channel.len())) - 1;
+
+        u8::try_from((value * 255 + max / 2) / max).ok()
+    });
+
+    let color = [channels.next()??, channels.next()??, channels.next()??, 255];
+
+    channels.next().is_none().then_some(color)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_x11_rgb;
+    use crate::{Error, Palette, color_palette};
+
+    #[test]
+    fn xresources_block() {
+        let palette = Palette::from_xresources(
+            "! gruvbox
+            #define bg #282828
+            #ifdef COLOR
+            *.foreground: #ebdbb2
+            *.background: bg
+            *.color0:     bg
+            *color2:      rgb:98/97/1a
+            URxvt.color1: #cc241d
+            *.color2:     #98971a
+            XTerm*color10: #b8bb26
+            *.cursorColor: #ffffff
+            #endif",
+        )
+        .unwrap();
+
+        assert_eq!(
+            palette,
+            color_palette!(
+                [0x28, 0x28, 0x28],
+                [0xcc, 0x24, 0x1d],
+                [0x98, 0x97, 0x1a],
+                [0xb8, 0xbb, 0x26]
+            )
+        );
+
+        assert!(matches!(
+            Palette::from_xresources("*.color0: #zzzzzz"),
+            Err(Error::InvalidPaletteData(_))
+        ));
+    }
+
+    #[test]
+    fn x11_rgb_colors() {
+        assert_eq!(parse_x11_rgb("rgb:ff/80/00"), Some([255, 128, 0, 255]));
+        assert_eq!(parse_x11_rgb("rgb:f/8/0"), Some([255, 136, 0, 255]));
+        assert_eq!(
+            parse_x11_rgb("rgb:ffff/0000/8080"),
+            Some([255, 0, 128, 255])
+        );
+
+        assert_eq!(parse_x11_rgb("rgb:ff/80"), None);
+        assert_eq!(parse_x11_rgb("rgb:ff/80/00/00"), None);
+        assert_eq!(parse_x11_rgb("rgb:fffff/0/0"), None);
+        assert_eq!(parse_x11_rgb("#ff8000"), None);
+    }
+}
@@ -0,0 +1,758 @@
+//! Strategies for finding the closest color in a palette
+//!
+//! All strategies implement [`NearestSearch`] and must produce the exact same results as
+//! [`closest_color_in_palette`]. Including which color is chosen, if two colors in the palette are
+//! equally close. The exceptions are [`LutSearch`], which trades exactness for speed, and
+//! [`LuminanceSearch`], which only compares brightness.
+//!
+//! Usually there is no need to pick a strategy manually, since [`map_image_to_palette`] chooses
+//! one based on the palette and algorithm. [`map_image_to_palette_with_search`] allows using a
+//! specific one, including custom implementations.
+//!
+//! [`closest_color_in_palette`]: crate::closest_color_in_palette
+//! [`map_image_to_palette`]: crate::map_image_to_palette
+//! [`map_image_to_palette_with_search`]: crate::map_image_to_palette_with_search
+use image::Rgba;
+
+use crate::{
+    closest_color_in_palette,
+    distance::DistanceAlgorithm,
+    palette::{LuminanceCoefficients, Palette},
+};
+
+/// Number of bits per channel used as the key of a [`LutSearch`]
+const LUT_BITS: u32 = 5;
+/// Maximum palette size a [`LutSearch`] can be created for
+const LUT_MAX_COLORS: usize = 16;
+
+/// Minimum palette size for which [`KdTreeSearch`] is chosen automatically
+///
+/// For smaller palettes simply checking every color is faster.
+const KD_TREE_MIN_COLORS: usize = 32;
+
+/// Maximum number of candidates a [`Composite`] keeps for colors smaller than the palette
+///
+/// The candidates are kept on the stack, so no memory is allocated per pixel.
+const COMPOSITE_MAX_CANDIDATES: usize = 32;
+
+/// A strategy for finding the closest color in a palette
+///
+/// Implementors are created for a specific palette. They must return the same color as
+/// [`closest_color_in_palette`] would for that palette, including when several colors are
+/// equally close. In that case the color coming first in the palette is returned.
+///
+/// [`LutSearch`] and [`LuminanceSearch`] are the only exceptions to this.
+pub trait NearestSearch {
+    /// Find the closest color to `color`
+    ///
+    /// Returns [`None`] if the palette is empty.
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>>;
+}
+
+/// Check every color in the palette
+///
+/// This works for any algorithm and is fastest for small palettes.
+pub struct LinearSearch<'p> {
+    /// The palette to search
+    palette: &'p Palette,
+}
+
+impl<'p> LinearSearch<'p> {
+    /// Create a new [`LinearSearch`]
+    #[must_use]
+    pub const fn new(palette: &'p Palette) -> Self {
+        Self { palette }
+    }
+}
+
+impl NearestSearch for LinearSearch<'_> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>> {
+        closest_color_in_palette(&color, self.palette, algorithm)
+    }
+}
+
+/// A [k-d tree](https://en.wikipedia.org/wiki/K-d_tree) over the red, green, blue and alpha
+/// channels of the palette
+///
+/// Whole branches of the tree are skipped, once [`DistanceAlgorithm::channel_lower_bound`] proves
+/// they can't contain a closer color. This makes it a lot faster than a [`LinearSearch`] for large
+/// palettes. For algorithms without a lower bound every color is checked.
+pub struct KdTreeSearch<'p> {
+    /// The palette colors with their index in the palette
+    ///
+    /// This is an implicit tree: the median of every slice is the node splitting it, with the
+    /// colors before and after it making up the two branches.
+    entries: Vec<(usize, &'p Rgba<u8>)>,
+}
+
+impl<'p> KdTreeSearch<'p> {
+    /// Create a new [`KdTreeSearch`]
+    #[must_use]
+    pub fn new(palette: &'p Palette) -> Self {
+        let mut entries: Vec<_> = palette.iter().enumerate().collect();
+
+        Self::build(&mut entries, 0);
+
+        Self { entries }
+    }
+
+    /// Arrange `entries` into a tree, splitting on the channel `depth % 4`
+    fn build(entries: &mut [(usize, &'p Rgba<u8>)], depth: usize) {
+        if entries.len() <= 1 {
+            return;
+        }
+
+        let axis = depth % 4;
+        let mid = entries.len() / 2;
+
+        entries.select_nth_unstable_by_key(mid, |(_, c)| c[axis]);
+
+        let (before, after) = entries.split_at_mut(mid);
+
+        Self::build(before, depth + 1);
+        Self::build(&mut after[1..], depth + 1);
+    }
+
+    /// Search the (sub-)tree `entries` for a color closer than `best`
+    fn search<D: DistanceAlgorithm>(
+        entries: &[(usize, &'p Rgba<u8>)],
+        depth: usize,
+        color: Rgba<u8>,
+        algorithm: &D,
+        best: &mut Option<(u32, usize, &'p Rgba<u8>)>,
+    ) {
+        if entries.is_empty() {
+            return;
+        }
+
+        let axis = depth % 4;
+        let mid = entries.len() / 2;
+        let (index, pcolor) = entries[mid];
+
+        let dist = algorithm.distance(&color, pcolor);
+
+        if best.is_none_or(|best| (dist, index) < (best.0, best.1)) {
+            *best = Some((dist, index, pcolor));
+        }
+
+        let (before, after) = (&entries[..mid], &entries[mid + 1..]);
+
+        let (near, far) = if color[axis] < pcolor[axis] {
+            (before, after)
+        } else {
+            (after, before)
+        };
+
+        Self::search(near, depth + 1, color, algorithm, best);
+
+        // Every color in `far` differs from `color` by at least this much in `axis`
+        let lower_bound = algorithm.channel_lower_bound(color[axis].abs_diff(pcolor[axis]));
+
+        if !lower_bound.is_some_and(|bound| best.is_some_and(|(dist, _, _)| bound > dist)) {
+            Self::search(far, depth + 1, color, algorithm, best);
+        }
+    }
+}
+
+impl NearestSearch for KdTreeSearch<'_> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>> {
+        let mut best = None;
+
+        Self::search(&self.entries, 0, color, algorithm, &mut best);
+
+        best.map(|(_, _, c)| c)
+    }
+}
+
+/// A grayscale palette sorted by brightness
+///
+/// Since all colors in the palette are gray, they lie on a single line through the color space.
+/// This allows finding the closest color via a binary search, followed by only checking the
+/// neighboring grays until [`DistanceAlgorithm::channel_lower_bound`] proves no closer gray can
+/// exist. For algorithms without a lower bound every gray is checked.
+pub struct GrayRamp<'p> {
+    /// The palette colors with their index in the palette, sorted by their gray value
+    entries: Vec<(usize, &'p Rgba<u8>)>,
+}
+
+impl<'p> GrayRamp<'p> {
+    /// Create a new [`GrayRamp`]
+    ///
+    /// Returns [`None`] if the palette isn't [grayscale](Palette::is_grayscale).
+    #[must_use]
+    pub fn new(palette: &'p Palette) -> Option<Self> {
+        if !palette.is_grayscale() {
+            return None;
+        }
+
+        let mut entries: Vec<_> = palette.iter().enumerate().collect();
+
+        // stable, so equal grays keep their palette order
+        entries.sort_by_key(|(_, c)| c[0]);
+
+        Some(Self { entries })
+    }
+}
+
+impl<'p> NearestSearch for GrayRamp<'p> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>> {
+        let min_channel = color[0].min(color[1]).min(color[2]);
+        let max_channel = color[0].max(color[1]).max(color[2]);
+
+        // The gray with the smallest maximum channel difference to `color`
+        let mid = u8::midpoint(min_channel, max_channel);
+
+        let start = self.entries.partition_point(|(_, c)| c[0] < mid);
+
+        // (distance, palette index, color)
+        let mut best: Option<(u32, usize, &'p Rgba<u8>)> = None;
+
+        let mut visit = |index: usize, pcolor: &'p Rgba<u8>| {
+            let spread = pcolor[0]
+                .abs_diff(min_channel)
+                .max(pcolor[0].abs_diff(max_channel));
+
+            let lower_bound = algorithm.channel_lower_bound(spread);
+
+            if lower_bound.is_some_and(|bound| best.is_some_and(|(dist, _, _)| bound > dist)) {
+                // All grays further along are at least as far away
+                return false;
+            }
+
+            let dist = algorithm.distance(&color, pcolor);
+
+            if best.is_none_or(|best| (dist, index) < (best.0, best.1)) {
+                best = Some((dist, index, pcolor));
+            }
+
+            true
+        };
+
+        for &(index, pcolor) in &self.entries[start..] {
+            if !visit(index, pcolor) {
+                break;
+            }
+        }
+
+        for &(index, pcolor) in self.entries[..start].iter().rev() {
+            if !visit(index, pcolor) {
+                break;
+            }
+        }
+
+        best.map(|(_, _, c)| c)
+    }
+}
+
+/// A precomputed lookup table of the closest color for every quantized color
+///
+/// The table is keyed on the top 5 bits of the red, green and blue channels, so it has 32³
+/// entries. Each one holds the color closest to the center of the range of colors it covers. After
+/// building the table once, finding the closest color is a single lookup, which makes this ideal
+/// for mapping many images to the same small palette.
+///
+/// ## Approximation
+///
+/// All colors within an entry share the same result, even though the closest color may differ
+/// for the ones near the borders between two palette colors. Every channel of a color is at most
+/// 4 away from the center of its entry. For metric algorithms the chosen color is therefore at
+/// most twice the distance to the center further away than the actual closest color. For the
+/// [`EuclideanDistance`](crate::distance::EuclideanDistance) this is `2 * sqrt(48)` (before
+/// squaring), for the [`ManhattanDistance`](crate::distance::ManhattanDistance) `24`. Use an exact
+/// search where this isn't acceptable.
+///
+/// Colors which aren't fully opaque are not in the table. These are looked up by checking every
+/// color in the palette instead.
+///
+/// Every search must use the same algorithm the table was built with.
+pub struct LutSearch<'p> {
+    /// The palette to search
+    palette: &'p Palette,
+    /// The index of the closest palette color for every quantized color
+    table: Box<[u8]>,
+}
+
+impl<'p> LutSearch<'p> {
+    /// Build the lookup table for `palette` using `algorithm`
+    ///
+    /// This is meant for metric algorithms such as the
+    /// [`EuclideanDistance`](crate::distance::EuclideanDistance) and
+    /// [`ManhattanDistance`](crate::distance::ManhattanDistance), for which the closest colors of
+    /// neighboring entries are related.
+    ///
+    /// Returns [`None`] if the palette is empty or contains more than 16 colors.
+    #[must_use]
+    pub fn new<D: DistanceAlgorithm>(palette: &'p Palette, algorithm: &D) -> Option<Self> {
+        if palette.is_empty() || palette.len() > LUT_MAX_COLORS {
+            return None;
+        }
+
+        let levels = 1_u8 << LUT_BITS;
+        let shift = 8 - LUT_BITS;
+        let center = 1_u8 << (shift - 1);
+
+        let table = (0..levels)
+            .flat_map(|r| (0..levels).flat_map(move |g| (0..levels).map(move |b| [r, g, b])))
+            .map(|key| {
+                let color = Rgba([
+                    (key[0] << shift) | center,
+                    (key[1] << shift) | center,
+                    (key[2] << shift) | center,
+                    255,
+                ]);
+
+                (0..=u8::MAX)
+                    .zip(palette)
+                    .min_by_key(|(_, pcolor)| algorithm.distance(&color, pcolor))
+                    .map_or(0, |(i, _)| i)
+            })
+            .collect();
+
+        Some(Self { palette, table })
+    }
+
+    /// Position of `color` in the table
+    fn key(color: Rgba<u8>) -> usize {
+        let shift = 8 - LUT_BITS;
+
+        color.0[..3].iter().fold(0, |key, &channel| {
+            (key << LUT_BITS) | usize::from(channel >> shift)
+        })
+    }
+}
+
+impl NearestSearch for LutSearch<'_> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>> {
+        if color[3] != 255 {
+            return closest_color_in_palette(&color, self.palette, algorithm);
+        }
+
+        self.palette
+            .iter()
+            .nth(usize::from(self.table[Self::key(color)]))
+    }
+}
+
+/// Find the palette color with the closest luminance, ignoring hue and saturation
+///
+/// The luminance is the same as for [`Palette::sort_by_luminance`]. Since the palette colors are
+/// ordered along a single axis, this mapping is monotone: a color darker than another is never
+/// mapped to a brighter palette color than the other. Nearest-color search can't guarantee this,
+/// which may invert the tones of grayscale or tonal images near the boundaries between palette
+/// colors.
+///
+/// The algorithm is ignored. If several palette colors have the same luminance, the first one in
+/// the palette is returned.
+///
+/// See [`MapOptions::monotone`](crate::MapOptions::monotone)
+pub struct LuminanceSearch<'p> {
+    /// The palette colors with their luminance, sorted by luminance
+    ///
+    /// Colors with the same luminance keep their palette order.
+    entries: Vec<(f32, &'p Rgba<u8>)>,
+    /// How the luminance of colors is computed
+    coefficients: LuminanceCoefficients,
+}
+
+impl<'p> LuminanceSearch<'p> {
+    /// Create a new [`LuminanceSearch`], using the default [`LuminanceCoefficients`]
+    #[must_use]
+    pub fn new(palette: &'p Palette) -> Self {
+        Self::with_coefficients(palette, LuminanceCoefficients::default())
+    }
+
+    /// Create a new [`LuminanceSearch`] computing the luminance with `coefficients`
+    #[must_use]
+    pub fn with_coefficients(palette: &'p Palette, coefficients: LuminanceCoefficients) -> Self {
+        let mut entries: Vec<_> = palette
+            .iter()
+            .map(|c| (coefficients.luminance(*c), c))
+            .collect();
+
+        // stable, so equal luminances keep their palette order
+        entries.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        Self {
+            entries,
+            coefficients,
+        }
+    }
+}
+
+impl NearestSearch for LuminanceSearch<'_> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, _: &D) -> Option<&Rgba<u8>> {
+        let y = self.coefficients.luminance(color);
+
+        let above = self.entries.partition_point(|(l, _)| *l < y);
+
+        // The first entry of the run of equal luminances directly below `y`
+        let below = above.checked_sub(1).map(|i| {
+            let l = self.entries[i].0;
+
+            self.entries.partition_point(|(other, _)| *other < l)
+        });
+
+        let closest = match (below, self.entries.get(above)) {
+            (Some(below), Some(&(l, _))) if l - y < y - self.entries[below].0 => above,
+            (Some(below), _) => below,
+            (None, _) => above,
+        };
+
+        self.entries.get(closest).map(|(_, c)| *c)
+    }
+}
+
+/// Narrow the palette down with a fast coarse algorithm, then pick among the remaining colors with
+/// the precise algorithm
+///
+/// For every color, the `k` palette colors closest according to the coarse algorithm `A` are kept
+/// as candidates. Only these are compared with the algorithm passed to
+/// [`NearestSearch::nearest`]. This combines the speed of e.g. the
+/// [`EuclideanDistance`](crate::distance::EuclideanDistance) with the accuracy of
+/// [`CIE76`](crate::distance::CIE76) for large palettes, where the precise algorithm would
+/// otherwise be calculated for every color.
+///
+/// The result is only exact if the closest color is among the candidates. With `k` at least the
+/// size of the palette, this always returns the same color as [`closest_color_in_palette`] with
+/// the precise algorithm. Otherwise at most 32 candidates are kept. Of equally close candidates,
+/// the first one in the palette is returned.
+pub struct Composite<'p, A: DistanceAlgorithm> {
+    /// The palette to search
+    palette: &'p Palette,
+    /// The coarse algorithm, used to select the candidates
+    coarse: A,
+    /// Number of candidates
+    k: usize,
+}
+
+impl<'p, A: DistanceAlgorithm> Composite<'p, A> {
+    /// Create a new [`Composite`] keeping the `k` closest colors according to `coarse`
+    ///
+    /// A `k` of `0` is treated as `1`, which is the same as only using `coarse`.
+    #[must_use]
+    pub fn new(palette: &'p Palette, coarse: A, k: usize) -> Self {
+        Self {
+            palette,
+            coarse,
+            k: k.max(1),
+        }
+    }
+}
+
+impl<A: DistanceAlgorithm> NearestSearch for Composite<'_, A> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>> {
+        // every color is a candidate
+        if self.k >= self.palette.len() {
+            return self
+                .palette
+                .iter()
+                .min_by_key(|pcolor| algorithm.distance(&color, pcolor));
+        }
+
+        let k = self.k.min(COMPOSITE_MAX_CANDIDATES);
+        let first = self.palette.iter().next()?;
+
+        // (coarse distance, palette index, color) of the closest colors so far, sorted by
+        // distance and index. Only the first `len` entries are used.
+        let mut candidates = [(u32::MAX, 0, first); COMPOSITE_MAX_CANDIDATES];
+        let mut len = 0;
+
+        for (index, pcolor) in self.palette.iter().enumerate() {
+            let dist = self.coarse.distance(&color, pcolor);
+
+            if len == k && dist >= candidates[k - 1].0 {
+                continue;
+            }
+
+            // after the equally close candidates, which come first in the palette
+            let pos = candidates[..len].partition_point(|&(other, ..)| other <= dist);
+
+            len = (len + 1).min(k);
+            candidates[pos..len].rotate_right(1);
+            candidates[pos] = (dist, index, pcolor);
+        }
+
+        candidates[..len]
+            .iter()
+            .min_by_key(|&&(_, index, pcolor)| (algorithm.distance(&color, pcolor), index))
+            .map(|&(.., pcolor)| pcolor)
+    }
+}
+
+/// The strategy chosen automatically, based on the palette and algorithm
+///
+/// [`GrayRamp`] and [`KdTreeSearch`] are only chosen for algorithms with a
+/// [lower bound](DistanceAlgorithm::channel_lower_bound), since they would otherwise have to check
+/// every color as well.
+pub(crate) enum AutoSearch<'p> {
+    /// See [`LinearSearch`]
+    Linear(LinearSearch<'p>),
+    /// See [`GrayRamp`]
+    GrayRamp(GrayRamp<'p>),
+    /// See [`KdTreeSearch`]
+    KdTree(KdTreeSearch<'p>),
+}
+
+impl<'p> AutoSearch<'p> {
+    /// Choose the fastest strategy for `palette` and `algorithm`
+    pub(crate) fn new<D: DistanceAlgorithm>(palette: &'p Palette, algorithm: &D) -> Self {
+        if algorithm.channel_lower_bound(0).is_none() {
+            return Self::Linear(LinearSearch::new(palette));
+        }
+
+        if let Some(ramp) = GrayRamp::new(palette) {
+            return Self::GrayRamp(ramp);
+        }
+
+        if palette.len() >= KD_TREE_MIN_COLORS {
+            return Self::KdTree(KdTreeSearch::new(palette));
+        }
+
+        Self::Linear(LinearSearch::new(palette))
+    }
+}
+
+impl NearestSearch for AutoSearch<'_> {
+    fn nearest<D: DistanceAlgorithm>(&self, color: Rgba<u8>, algorithm: &D) -> Option<&Rgba<u8>> {
+        match self {
+            Self::Linear(search) => search.nearest(color, algorithm),
+            Self::GrayRamp(search) => search.nearest(color, algorithm),
+            Self::KdTree(search) => search.nearest(color, algorithm),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AutoSearch, Composite, GrayRamp, KdTreeSearch, LinearSearch, LuminanceSearch, LutSearch,
+        NearestSearch,
+    };
+    use crate::{
+        Palette, closest_color_in_palette, color_palette,
+        distance::{
+            Algorithms, CIE76, DistanceAlgorithm, EuclideanDistance, ManhattanDistance,
+            WeightedEuclideanDistance,
+        },
+        rgba,
+    };
+    use image::Rgba;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use strum::IntoEnumIterator;
+
+    /// Assert `search` finds the exact same colors as [`closest_color_in_palette`]
+    fn assert_parity(
+        search: &impl NearestSearch,
+        palette: &Palette,
+        algorithm: &impl DistanceAlgorithm,
+    ) {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(17) {
+                for b in (0..=255).step_by(51) {
+                    for alpha in [0, 100, 255] {
+                        let color = rgba!(r, g, b, alpha);
+
+                        assert!(std::ptr::eq(
+                            search.nearest(color, algorithm).unwrap(),
+                            closest_color_in_palette(&color, palette, algorithm).unwrap()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gray_ramp_matches_linear_scan() {
+        let palette = color_palette!(
+            [200, 200, 200],
+            [0, 0, 0],
+            [128, 128, 128, 100],
+            [64, 64, 64],
+            [128, 128, 128],
+            [255, 255, 255],
+            [64, 64, 64],
+            [30, 30, 30, 0]
+        );
+
+        let ramp = GrayRamp::new(&palette).unwrap();
+
+        for algorithm in Algorithms::iter() {
+            assert_parity(&ramp, &palette, &algorithm);
+        }
+    }
+
+    #[test]
+    fn gray_ramp_requires_grayscale() {
+        let gray = color_palette!([0, 0, 0], [255, 255, 255]);
+        let colored = color_palette!([0, 0, 0], [255, 0, 0]);
+
+        assert!(GrayRamp::new(&gray).is_some());
+        assert!(GrayRamp::new(&colored).is_none());
+    }
+
+    #[test]
+    fn backends_match_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(1508);
+
+        let mut colors: Vec<Rgba<u8>> = (0..100).map(|_| Rgba(rng.random())).collect();
+        // duplicates, to check ties are broken the same way
+        colors.extend_from_within(10..30);
+
+        let palette = Palette::from(colors);
+
+        let kd_tree = KdTreeSearch::new(&palette);
+        let linear = LinearSearch::new(&palette);
+
+        for algorithm in Algorithms::iter() {
+            assert_parity(&kd_tree, &palette, &algorithm);
+            assert_parity(&linear, &palette, &algorithm);
+            assert_parity(&AutoSearch::new(&palette, &algorithm), &palette, &algorithm);
+        }
+
+        // the lower bound has to account for the reduced weight of alpha
+        let weighted = WeightedEuclideanDistance::new(0.3);
+        assert_parity(&kd_tree, &palette, &weighted);
+    }
+
+    #[test]
+    fn composite_with_full_k_matches_fine_algorithm() {
+        let mut rng = StdRng::seed_from_u64(1530);
+
+        let mut colors: Vec<Rgba<u8>> = (0..60).map(|_| Rgba(rng.random())).collect();
+        colors.extend_from_within(5..15);
+
+        let palette = Palette::from(colors);
+
+        let composite = Composite::new(&palette, EuclideanDistance, palette.len());
+
+        for algorithm in Algorithms::iter() {
+            assert_parity(&composite, &palette, &algorithm);
+        }
+
+        // fewer candidates than colors, also beyond the 32 kept at most
+        for k in [5, 40] {
+            let composite = Composite::new(&palette, EuclideanDistance, k);
+
+            for _ in 0..200 {
+                let color = Rgba(rng.random());
+
+                let mut candidates: Vec<_> = palette.iter().enumerate().collect();
+                candidates.sort_by_key(|&(index, pcolor)| {
+                    (EuclideanDistance.distance(&color, pcolor), index)
+                });
+                candidates.truncate(k.min(32));
+
+                let expected = candidates
+                    .into_iter()
+                    .min_by_key(|&(index, pcolor)| (CIE76.distance(&color, pcolor), index))
+                    .map(|(_, pcolor)| pcolor);
+
+                assert_eq!(composite.nearest(color, &CIE76), expected);
+            }
+        }
+
+        // a single candidate is just the coarse algorithm
+        let coarse_only = Composite::new(&palette, EuclideanDistance, 1);
+
+        for _ in 0..1000 {
+            let color = Rgba(rng.random());
+
+            assert_eq!(
+                coarse_only.nearest(color, &Algorithms::CIE76),
+                closest_color_in_palette(&color, &palette, &EuclideanDistance)
+            );
+        }
+    }
+
+    #[test]
+    fn auto_search_choice() {
+        let gray = color_palette!([0, 0, 0], [255, 255, 255]);
+        let large = Palette::from((0..=255).map(|v| rgba!(v, 0, 0)).collect::<Vec<_>>());
+
+        assert!(matches!(
+            AutoSearch::new(&gray, &Algorithms::EuclideanDistance),
+            AutoSearch::GrayRamp(_)
+        ));
+        assert!(matches!(
+            AutoSearch::new(&large, &Algorithms::ManhattanDistance),
+            AutoSearch::KdTree(_)
+        ));
+        assert!(matches!(
+            AutoSearch::new(&large, &Algorithms::CIE76),
+            AutoSearch::Linear(_)
+        ));
+    }
+
+    #[test]
+    fn lut_within_quantization_tolerance() {
+        let mut rng = StdRng::seed_from_u64(1511);
+
+        let palette = Palette::from((0..16).map(|_| Rgba(rng.random())).collect::<Vec<_>>());
+
+        let euclidean = LutSearch::new(&palette, &EuclideanDistance).unwrap();
+        let manhattan = LutSearch::new(&palette, &ManhattanDistance).unwrap();
+
+        for _ in 0..10_000 {
+            let color = rgba!(rng.random(), rng.random(), rng.random());
+
+            let exact = closest_color_in_palette(&color, &palette, &EuclideanDistance).unwrap();
+            let approx = euclidean.nearest(color, &EuclideanDistance).unwrap();
+
+            let exact = f64::from(EuclideanDistance.distance(&color, exact)).sqrt();
+            let approx = f64::from(EuclideanDistance.distance(&color, approx)).sqrt();
+
+            assert!(approx <= 2.0f64.mul_add(48.0f64.sqrt(), exact));
+
+            let exact = closest_color_in_palette(&color, &palette, &ManhattanDistance).unwrap();
+            let approx = manhattan.nearest(color, &ManhattanDistance).unwrap();
+
+            assert!(
+                ManhattanDistance.distance(&color, approx)
+                    <= ManhattanDistance.distance(&color, exact) + 2 * 12
+            );
+        }
+
+        // the center of every entry is exact
+        assert!(std::ptr::eq(
+            euclidean
+                .nearest(rgba!(4, 132, 252), &EuclideanDistance)
+                .unwrap(),
+            closest_color_in_palette(&rgba!(4, 132, 252), &palette, &EuclideanDistance).unwrap()
+        ));
+    }
+
+    #[test]
+    fn lut_palette_size() {
+        let small = color_palette!([0, 0, 0], [255, 255, 255]);
+        let large = Palette::from((0..17).map(|v| rgba!(v, 0, 0)).collect::<Vec<_>>());
+
+        assert!(LutSearch::new(&small, &EuclideanDistance).is_some());
+        assert!(LutSearch::new(&large, &EuclideanDistance).is_none());
+        assert!(LutSearch::new(&Palette::from(Vec::new()), &EuclideanDistance).is_none());
+    }
+
+    #[test]
+    fn luminance_search_ties() {
+        let palette = color_palette!([0, 0, 0], [50, 50, 50], [50, 50, 50, 0], [255, 255, 255]);
+
+        let search = LuminanceSearch::new(&palette);
+
+        let nearest = |c| search.nearest(c, &EuclideanDistance).unwrap();
+
+        assert!(std::ptr::eq(
+            nearest(rgba!(50, 50, 50)),
+            palette.iter().nth(1).unwrap()
+        ));
+        assert!(std::ptr::eq(
+            nearest(rgba!(60, 60, 60)),
+            palette.iter().nth(1).unwrap()
+        ));
+        assert!(std::ptr::eq(
+            nearest(rgba!(40, 40, 40)),
+            palette.iter().nth(1).unwrap()
+        ));
+        assert_eq!(nearest(rgba!(3, 3, 3)), &rgba!(0, 0, 0));
+        assert_eq!(nearest(rgba!(250, 250, 250)), &rgba!(255, 255, 255));
+    }
+}
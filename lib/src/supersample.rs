@@ -0,0 +1,122 @@
+//! Supersampled mapping, for anti-aliased thumbnails
+//!
+//! Mapping an image which has already been scaled down results in hard, jagged edges between the
+//! regions of different palette colors. Mapping at full resolution and scaling the result down
+//! afterwards blends the colors along these edges instead.
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::{distance::DistanceAlgorithm, mapped_to_palette, palette::Palette};
+
+/// Map `img` at full resolution, then scale it down by `factor` using a box filter
+///
+/// Every pixel of the result is the average of a `factor` x `factor` block of the mapped image, so
+/// the result is `factor` times smaller in both dimensions (rounded up). Pixels inside a region of
+/// one palette color keep that color, while the pixels along the edges between regions are blends
+/// of the neighboring colors. This means the result is no longer restricted to the palette.
+///
+/// A `factor` of `0` or `1` returns the mapped image as is.
+///
+/// ## Panics
+///
+/// This function panics if `palette` doesn't contain any colors.
+#[must_use]
+pub fn map_supersampled<D: DistanceAlgorithm + Sync>(
+    img: &DynamicImage,
+    palette: &Palette,
+    algorithm: &D,
+    factor: u32,
+) -> DynamicImage {
+    let mapped = mapped_to_palette(img, palette, algorithm);
+
+    if factor <= 1 {
+        return mapped;
+    }
+
+    DynamicImage::from(box_downscale(&mapped, factor))
+}
+
+/// Scale `img` down by `factor`, averaging every `factor` x `factor` block into one pixel
+///
+/// Blocks at the right and bottom edges may be smaller, if the dimensions aren't a multiple of
+/// `factor`.
+fn box_downscale(img: &DynamicImage, factor: u32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+
+    RgbaImage::from_fn(
+        width.div_ceil(factor),
+        height.div_ceil(factor),
+        |out_x, out_y| {
+            let xs = out_x * factor..((out_x + 1) * factor).min(width);
+            let ys = out_y * factor..((out_y + 1) * factor).min(height);
+
+            let count = xs.len() * ys.len();
+
+            let sums = ys.flat_map(|y| xs.clone().map(move |x| (x, y))).fold(
+                [0_usize; 4],
+                |mut sums, (x, y)| {
+                    let px = img.get_pixel(x, y);
+
+                    sums.iter_mut()
+                        .zip(px.0)
+                        .for_each(|(s, v)| *s += usize::from(v));
+
+                    sums
+                },
+            );
+
+            #[allow(
+                clippy::cast_possible_truncation,
+                reason = "The average of u8 values fits in a u8."
+            )]
+            Rgba(sums.map(|sum| ((sum + count / 2) / count) as u8))
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::map_supersampled;
+    use crate::{color_palette, distance::EuclideanDistance, mapped_to_palette};
+    use image::{DynamicImage, ImageReader, imageops::FilterType};
+    use std::{collections::HashSet, io::Cursor};
+
+    #[test]
+    fn supersampled_diagonal_gradient() {
+        let img = ImageReader::new(Cursor::new(include_bytes!(
+            "../../assets/test-imgs/diagonal_rgb_gradient_500x500.png"
+        )))
+        .with_guessed_format()
+        .unwrap()
+        .decode()
+        .unwrap();
+
+        let palette = color_palette!([0, 0, 0], [255, 0, 0], [0, 0, 255], [255, 255, 255]);
+
+        let supersampled = map_supersampled(&img, &palette, &EuclideanDistance, 4);
+
+        // the plain order: mapping, then resizing without blending
+        let plain = mapped_to_palette(&img, &palette, &EuclideanDistance).resize_exact(
+            supersampled.width(),
+            supersampled.height(),
+            FilterType::Nearest,
+        );
+
+        let colors = |img: &DynamicImage| {
+            img.to_rgba8()
+                .pixels()
+                .copied()
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        // the edges of the plain result only consist of palette colors, while the supersampled
+        // edges are blended
+        assert!(colors(&plain) <= palette.len());
+        assert!(colors(&supersampled) > palette.len());
+
+        let mut buf = Vec::new();
+        crate::encoding::write_png(&supersampled, &mut buf).unwrap();
+
+        insta::assert_binary_snapshot!(".png", buf);
+    }
+}
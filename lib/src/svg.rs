@@ -0,0 +1,112 @@
+//! Export images as SVG
+//!
+//! This is intended for (palette-mapped) pixel art. Every pixel becomes a rectangle, resulting in
+//! blocky output, which can be scaled to any size without becoming blurry.
+use image::{DynamicImage, Rgba};
+
+/// Convert an image to an SVG made up of one `<rect>` per horizontal run of identical pixels
+///
+/// Fully transparent pixels are left out. Partially transparent pixels are given a
+/// `fill-opacity`.
+///
+/// Since every run of pixels results in an element, this is only sensible for small images with
+/// few colors, such as ones mapped to a palette.
+#[must_use]
+pub fn to_svg(img: &DynamicImage) -> String {
+    let img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+
+    let mut out = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" shape-rendering="crispEdges">"#
+    );
+    out.push('\n');
+
+    for (y, row) in img.rows().enumerate() {
+        let mut x = 0;
+        let mut row = row.peekable();
+
+        while let Some(color) = row.next() {
+            let mut run = 1;
+
+            while row.next_if_eq(&color).is_some() {
+                run += 1;
+            }
+
+            if color[3] != 0 {
+                out.push_str(&rect(x, y, run, *color));
+            }
+
+            x += run;
+        }
+    }
+
+    out.push_str("</svg>\n");
+
+    out
+}
+
+/// A single `<rect>` of height 1
+fn rect(x: usize, y: usize, width: usize, color: Rgba<u8>) -> String {
+    let fill = format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]);
+
+    let opacity = if color[3] == 255 {
+        String::new()
+    } else {
+        format!(r#" fill-opacity="{:.3}""#, f32::from(color[3]) / 255.0)
+    };
+
+    format!(r#"<rect x="{x}" y="{y}" width="{width}" height="1" fill="{fill}"{opacity}/>"#) + "\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_svg;
+    use crate::{color_palette, distance::EuclideanDistance, map_image_to_palette, rgba};
+    use image::{DynamicImage, RgbaImage};
+    use std::collections::HashSet;
+
+    #[test]
+    fn svg_fill_colors() {
+        let mut img = RgbaImage::new(16, 16);
+
+        for (x, y, px) in img.enumerate_pixels_mut() {
+            *px = rgba!(
+                u8::try_from(x * 16).unwrap(),
+                u8::try_from(y * 16).unwrap(),
+                0
+            );
+        }
+
+        let mut img = DynamicImage::from(img);
+
+        map_image_to_palette(
+            &mut img,
+            &color_palette!([0, 0, 0], [255, 0, 0], [0, 255, 0]),
+            &EuclideanDistance,
+        );
+
+        let svg = to_svg(&img);
+
+        let fills: HashSet<_> = svg.split("fill=\"").skip(1).map(|s| &s[..7]).collect();
+
+        assert_eq!(fills, HashSet::from(["#000000", "#ff0000", "#00ff00"]));
+        // run-length encoded, so there are at most 3 rects per row
+        assert!(svg.matches("<rect").count() <= 16 * 3);
+    }
+
+    #[test]
+    fn svg_transparency() {
+        let mut img = RgbaImage::new(3, 1);
+        img.put_pixel(0, 0, rgba!(255, 0, 0, 0));
+        img.put_pixel(1, 0, rgba!(255, 0, 0, 51));
+        img.put_pixel(2, 0, rgba!(255, 0, 0));
+
+        let svg = to_svg(&DynamicImage::from(img));
+
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains(
+            r##"<rect x="1" y="0" width="1" height="1" fill="#ff0000" fill-opacity="0.200"/>"##
+        ));
+        assert!(svg.contains(r##"<rect x="2" y="0" width="1" height="1" fill="#ff0000"/>"##));
+    }
+}
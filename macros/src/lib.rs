@@ -142,6 +142,13 @@ pub fn algorithms(input: TokenStream) -> TokenStream {
         }
     });
 
+    let lower_bound_arms = algorithms.iter().map(|a| {
+        let ident = &a.ident;
+        quote! {
+            Algorithms::#ident => #ident::default().channel_lower_bound(delta)
+        }
+    });
+
     // Generate the concrete algorithm structs, preserving
     // the original doc comments verbatim.
     let structs = algorithms.iter().map(|a| {
@@ -177,6 +184,12 @@ pub fn algorithms(input: TokenStream) -> TokenStream {
                     #( #match_arms, )*
                 }
             }
+
+            fn channel_lower_bound(&self, delta: u8) -> Option<u32> {
+                match self {
+                    #( #lower_bound_arms, )*
+                }
+            }
         }
 
         #( #structs )*
@@ -334,9 +347,7 @@ fn algorithm_tests(algorithm: &Algorithm, token_stream: &mut proc_macro2::TokenS
                     map_image_to_palette(&mut img, &TESTING_PALETTE, &#algorithm::default());
                     let mut buf = Vec::new();
 
-                    let encoder = image::codecs::png::PngEncoder::new(&mut buf);
-
-                    img.write_with_encoder(encoder).expect("Failed to encode.");
+                    crate::encoding::write_png(&img, &mut buf).expect("Failed to encode.");
 
                     insta::assert_binary_snapshot!(".png", buf);
                 }
@@ -7,10 +7,22 @@
 //! 2. This file reads those files during the build and creates the `lib.rs` file. This is done
 //!    using [`askama`] as a templating system.
 //!
+//!    Theme files either contain an object with the scheme's metadata and its colors, or (in older
+//!    versions of the script) just the array of colors:
+//!
+//!    ```json
+//!    {"author":"...","system":"base16","variant":"dark","palette":["#282a36", ...]}
+//!    ```
+//!
+//!    If the variant is missing it is derived from the luminance of the background color (`base00`).
+//!    The currently vendored files predate the metadata, so all variants are derived and no
+//!    authors are known until they are regenerated.
+//!
 //! 3. The library can be used like any other. Containing a `Base16` and `Base24` enum with all of
 //!    themes, which can be converted into [`palette_mapper::Palette`]s simply by using `.into()`
 use askama::Template;
-use palette_mapper::Palette;
+use palette_mapper::{Palette, palette::LuminanceCoefficients};
+use serde_json::Value;
 
 use std::{
     env,
@@ -41,14 +53,73 @@ fn get_themes(path: impl AsRef<Path>) -> Vec<Theme> {
                 .read_to_string(&mut content)
                 .expect("Should not fail to read theme file.");
 
-            Theme {
-                name,
-                palette: serde_json::from_str(&content).unwrap(),
-            }
+            Theme::new(name, serde_json::from_str(&content).unwrap())
         })
         .collect()
 }
 
+/// Words naming a variant of a theme, rather than the theme itself
+///
+/// These are stripped from the end of theme names to find their family, e.g. `gruvbox-dark-hard`
+/// is part of the `gruvbox` family.
+const VARIANT_WORDS: &[&str] = &[
+    "black",
+    "contrast",
+    "dark",
+    "darker",
+    "dawn",
+    "dim",
+    "dimmed",
+    "frappe",
+    "hard",
+    "higher",
+    "latte",
+    "light",
+    "lighter",
+    "macchiato",
+    "medium",
+    "mirage",
+    "mocha",
+    "moon",
+    "pale",
+    "patched",
+    "plus",
+    "reversible",
+    "soft",
+    "storm",
+    "white",
+];
+
+/// The family of the theme called `name`, i.e. its name without any variant words at the end
+///
+/// The first word is always kept, so themes like `mocha` are their own family.
+fn family(name: &str) -> String {
+    let mut words: Vec<_> = name.split('-').collect();
+
+    while words.len() > 1 && words.last().is_some_and(|w| VARIANT_WORDS.contains(w)) {
+        words.pop();
+    }
+
+    words.join("-")
+}
+
+/// Determine if a palette is dark based on the luminance of its background color (`base00`)
+///
+/// The [Rec. 709](LuminanceCoefficients::Rec709) weights are applied to the gamma-encoded channels,
+/// so mid gray is the threshold between dark and light.
+fn is_dark_background(palette: &Palette) -> bool {
+    let bg = palette.iter().next().expect("Palette should not be empty.");
+
+    LuminanceCoefficients::Rec709
+        .weights()
+        .iter()
+        .zip(&bg.0[..3])
+        .fold(0.0, |total, (weight, &channel)| {
+            weight.mul_add(f32::from(channel), total)
+        })
+        < 128.0
+}
+
 fn main() {
     let base16 = get_themes("./base16/");
     let base24 = get_themes("./base24/");
@@ -119,4 +190,49 @@ struct Theme {
     name: String,
     /// Palette of the theme
     palette: Palette,
+    /// Author of the theme as a rust expression of type `Option<&'static str>`
+    author: String,
+    /// If the theme has a dark background
+    dark: bool,
+    /// The family of the theme, derived from the file name
+    family: String,
+}
+
+impl Theme {
+    /// Create a theme from the contents of its json file
+    fn new(name: String, content: Value) -> Self {
+        let (palette, metadata) = match content {
+            Value::Object(mut metadata) => (
+                metadata
+                    .remove("palette")
+                    .expect("Theme file should contain a palette."),
+                metadata,
+            ),
+            palette => (palette, serde_json::Map::new()),
+        };
+
+        let palette: Palette = serde_json::from_value(palette).unwrap();
+
+        let dark = match metadata.get("variant").and_then(Value::as_str) {
+            Some("dark") => true,
+            Some("light") => false,
+            _ => is_dark_background(&palette),
+        };
+
+        let author = format!(
+            "{:?}",
+            metadata
+                .get("author")
+                .and_then(Value::as_str)
+                .filter(|a| !a.is_empty())
+        );
+
+        Self {
+            family: family(&name),
+            name,
+            palette,
+            author,
+            dark,
+        }
+    }
 }
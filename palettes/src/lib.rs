@@ -13,7 +13,7 @@ include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 
 #[cfg(test)]
 mod test {
-    use super::{Base16, Base24};
+    use super::{Base16, Base24, BaseBoth};
     use palette_mapper::Palette;
     use strum::IntoEnumIterator;
 
@@ -28,4 +28,77 @@ mod test {
             assert_eq!(Palette::from(theme).len(), 24);
         }
     }
+
+    #[test]
+    fn select_palette_slots() {
+        let full: Vec<_> = Palette::from(Base16::Dracula).into_iter().collect();
+
+        let accents = Base16::Dracula.palette_slots(&[8, 9, 10, 11, 12, 13, 14, 15]);
+
+        assert_eq!(accents.len(), 8);
+        assert_eq!(accents, Palette::from(full[8..].to_vec()));
+
+        // order is kept and slots outside of the theme are skipped
+        let picked = Base24::Dracula.palette_slots(&[23, 0, 24, 100]);
+
+        assert_eq!(picked.len(), 2);
+        assert_eq!(
+            picked,
+            Palette::from(vec![
+                Palette::from(Base24::Dracula).into_iter().nth(23).unwrap(),
+                Palette::from(Base24::Dracula).into_iter().next().unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn theme_variants() {
+        assert!(Base16::Dracula.is_dark());
+        assert!(Base24::Dracula.is_dark());
+        assert!(BaseBoth::Base16dracula.is_dark());
+
+        assert!(!Base16::AtelierCaveLight.is_dark());
+        assert!(!Base16::CatppuccinLatte.is_dark());
+    }
+
+    #[test]
+    fn filter_themes_by_variant() {
+        assert!(Base16::dark_themes().any(|t| t == Base16::Dracula));
+        assert!(!Base16::dark_themes().any(|t| t == Base16::AtelierCaveLight));
+        assert!(Base16::light_themes().any(|t| t == Base16::AtelierCaveLight));
+
+        assert!(!Base24::dark_themes().any(|t| t == Base24::CatppuccinLatte));
+
+        assert_eq!(
+            Base16::dark_themes().count() + Base16::light_themes().count(),
+            Base16::iter().count()
+        );
+    }
+
+    #[test]
+    fn theme_families() {
+        let gruvbox = Base16::family_members("gruvbox");
+
+        assert_eq!(Base16::GruvboxDarkHard.family(), "gruvbox");
+        assert_eq!(Base16::GruvboxLightSoft.family(), "gruvbox");
+        assert!(gruvbox.contains(&Base16::GruvboxDarkHard));
+        assert!(gruvbox.contains(&Base16::GruvboxLightSoft));
+        assert!(gruvbox.iter().all(|t| t.family() == "gruvbox"));
+
+        // a separate family, even though the name starts the same
+        assert_eq!(Base16::GruvboxMaterialDarkHard.family(), "gruvbox-material");
+        assert!(!gruvbox.contains(&Base16::GruvboxMaterialDarkHard));
+
+        assert_eq!(
+            Base24::family_members("solarized"),
+            [
+                Base24::SolarizedDarkHigherContrast,
+                Base24::SolarizedDarkPatched
+            ]
+        );
+        assert_eq!(BaseBoth::Base16solarizedLight.family(), "solarized");
+        assert_eq!(Base16::Mocha.family(), "mocha");
+
+        assert!(Base16::family_members("no-such-family").is_empty());
+    }
 }
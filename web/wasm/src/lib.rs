@@ -2,14 +2,24 @@
 use std::io::Cursor;
 use std::str::FromStr;
 
-use image::ImageReader;
+use base64::Engine;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, ImageFormat, ImageReader, RgbaImage};
 use palette_mapper::distance::Algorithms;
-use palette_mapper::{Palette, map_image_to_palette};
+use palette_mapper::mask::blend_images;
+use palette_mapper::{Palette, try_map_image_to_palette};
 use palette_mapper_palettes::BaseBoth;
 use strum::IntoEnumIterator;
 
 use wasm_bindgen::prelude::*;
 
+/// Largest width and height of the frames of [`transition_gif`]
+const PREVIEW_SIZE: u32 = 256;
+/// Time every frame of [`transition_gif`] is shown, in milliseconds
+const FRAME_DELAY_MS: u32 = 80;
+/// Most frames [`transition_gif`] generates, which keeps the animation around five seconds long
+const MAX_FRAMES: u32 = 64;
+
 /// Return a list of all algorithms
 #[wasm_bindgen]
 #[must_use]
@@ -24,6 +34,17 @@ pub fn base_both() -> Vec<String> {
     BaseBoth::iter().map(|v| v.to_string()).collect()
 }
 
+/// Get all base* themes which are either dark or light
+#[wasm_bindgen]
+#[must_use]
+pub fn base_both_by_variant(dark: bool) -> Vec<String> {
+    if dark {
+        BaseBoth::dark_themes().map(|v| v.to_string()).collect()
+    } else {
+        BaseBoth::light_themes().map(|v| v.to_string()).collect()
+    }
+}
+
 /// Try to parse `theme` to [BaseBoth] theme
 #[wasm_bindgen]
 #[cfg(target_family = "wasm")]
@@ -66,12 +87,29 @@ pub enum MapErr {
 
     /// The passed string could not be converted to a theme
     InvalidThemeString,
+    /// The passed palette doesn't contain any colors
+    EmptyPalette,
+    /// The mapping was cancelled before it finished
+    Cancelled,
+}
+
+impl From<palette_mapper::Error> for MapErr {
+    fn from(value: palette_mapper::Error) -> Self {
+        match value {
+            palette_mapper::Error::EmptyPalette => Self::EmptyPalette,
+            palette_mapper::Error::Conversion(_) | palette_mapper::Error::InvalidPaletteData(_) => {
+                Self::InvalidPaletteString
+            }
+            palette_mapper::Error::Io(_) => Self::FailedToEncode,
+            palette_mapper::Error::Cancelled => Self::Cancelled,
+        }
+    }
 }
 
 /// Main function used for interfacing with the js code to facilitate the conversion of images
 ///
 /// This function takes both in very simple types and then converts them internally into the proper
-/// types to be passed to [`map_image_to_palette`], returning errors along the way. If used
+/// types to be passed to [`try_map_image_to_palette`], returning errors along the way. If used
 /// correctly this function **should** not error. This is not a guarantee.
 ///
 /// ## Errors
@@ -84,8 +122,119 @@ pub enum MapErr {
 /// future.
 #[wasm_bindgen]
 pub fn map_image(img: Vec<u8>, palette: &str, algorithm: &str) -> Result<Vec<u8>, MapErr> {
+    map_image_encoded(img, palette, algorithm).map(|(bytes, _)| bytes)
+}
+
+/// Same as [`map_image`], but returns the image as a `data:` URL
+///
+/// The URL can directly be used as the `src` of an `<img>`. The image keeps the format of the
+/// input.
+///
+/// ## Errors
+///
+/// See: [`MapErr`]
+#[wasm_bindgen]
+pub fn map_image_data_url(img: Vec<u8>, palette: &str, algorithm: &str) -> Result<String, MapErr> {
+    let (bytes, format) = map_image_encoded(img, palette, algorithm)?;
+
+    Ok(format!(
+        "data:{};base64,{}",
+        format.to_mime_type(),
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// A short animated GIF fading from the original image to the mapped one
+///
+/// The image is scaled down to at most 256x256 pixels, and `frames` frames are generated, blending
+/// the mapped image over the original at increasing opacity. The first frame is the original, the
+/// last one the fully mapped image. A single frame only shows the mapped image. At most 64 frames
+/// are generated. The animation loops forever.
+///
+/// ## Errors
+///
+/// See: [`MapErr`]
+#[wasm_bindgen]
+pub fn transition_gif(
+    img: Vec<u8>,
+    palette: &str,
+    algorithm: &str,
+    frames: u32,
+) -> Result<Vec<u8>, MapErr> {
+    let (original, _) = decode_image(img)?;
+
+    let original = if original.width() > PREVIEW_SIZE || original.height() > PREVIEW_SIZE {
+        original.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE)
+    } else {
+        original
+    };
+
+    let frames = transition_frames(&original, palette, algorithm, frames)?;
+
+    let mut output = Vec::new();
+
+    {
+        let mut encoder = GifEncoder::new(&mut output);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|_| MapErr::FailedToEncode)?;
+
+        encoder
+            .encode_frames(frames.into_iter().map(|frame| {
+                Frame::from_parts(frame, 0, 0, Delay::from_numer_denom_ms(FRAME_DELAY_MS, 1))
+            }))
+            .map_err(|_| MapErr::FailedToEncode)?;
+    }
+
+    Ok(output)
+}
+
+/// The frames of [`transition_gif`], from `original` to the mapped image
+///
+/// At least one and at most [`MAX_FRAMES`] frames are returned.
+fn transition_frames(
+    original: &DynamicImage,
+    palette: &str,
+    algorithm: &str,
+    frames: u32,
+) -> Result<Vec<RgbaImage>, MapErr> {
+    let mut mapped = original.clone();
+    map_decoded(&mut mapped, palette, algorithm)?;
+
+    let frames = frames.clamp(1, MAX_FRAMES);
+    let last = u64::from(frames - 1);
+
+    Ok((0..frames)
+        .map(|i| {
+            let strength = (u64::from(i) * 255).checked_div(last).unwrap_or(255);
+
+            blend_images(original, &mapped, u8::try_from(strength).unwrap_or(u8::MAX))
+        })
+        .collect())
+}
+
+/// Map the image and encode it in the format of the input
+///
+/// Returns the encoded image and its format. See [`map_image`].
+fn map_image_encoded(
+    img: Vec<u8>,
+    palette: &str,
+    algorithm: &str,
+) -> Result<(Vec<u8>, ImageFormat), MapErr> {
     let mut output = Cursor::new(Vec::with_capacity(img.len()));
 
+    let (mut buf, format) = decode_image(img)?;
+
+    map_decoded(&mut buf, palette, algorithm)?;
+
+    buf.write_to(&mut output, format)
+        .map_err(|_| MapErr::FailedToEncode)?;
+
+    Ok((output.into_inner(), format))
+}
+
+/// Decode the image, guessing its format from its contents
+fn decode_image(img: Vec<u8>) -> Result<(DynamicImage, ImageFormat), MapErr> {
     let reader = ImageReader::new(Cursor::new(img))
         .with_guessed_format()
         .map_err(|_| MapErr::FormatNotUnderstood)?;
@@ -94,18 +243,102 @@ pub fn map_image(img: Vec<u8>, palette: &str, algorithm: &str) -> Result<Vec<u8>
         .format()
         .expect("Since we called with guessed format the format must be known");
 
-    let mut buf = reader.decode().map_err(|_| MapErr::InvalidImg)?;
+    let buf = reader.decode().map_err(|_| MapErr::InvalidImg)?;
 
+    Ok((buf, format))
+}
+
+/// Parse `palette` and `algorithm` and map the decoded image with them
+fn map_decoded(img: &mut DynamicImage, palette: &str, algorithm: &str) -> Result<(), MapErr> {
     let pal: Palette = serde_json::from_str(palette).map_err(|_| MapErr::InvalidPaletteString)?;
 
-    map_image_to_palette(
-        &mut buf,
+    try_map_image_to_palette(
+        img,
         &pal,
         &Algorithms::from_str(algorithm).map_err(|_| MapErr::InvalidAlgorithm)?,
-    );
+    )?;
 
-    buf.write_to(&mut output, format)
-        .map_err(|_| MapErr::FailedToEncode)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{map_image_data_url, transition_frames, transition_gif};
+    use image::codecs::gif::GifDecoder;
+    use image::{AnimationDecoder, DynamicImage, ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    /// Palette for the transition tests
+    const PALETTE: &str = r##"["#000000", "#ffffff"]"##;
+
+    /// A red image with a white stripe, encoded as PNG
+    fn png() -> Vec<u8> {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::from_fn(8, 6, |x, _| {
+            if x < 2 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([200, 30, 30])
+            }
+        })
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+
+        png.into_inner()
+    }
+
+    #[test]
+    fn data_url() {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::from_pixel(3, 2, image::Rgb([10, 200, 30]))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+
+        let url = map_image_data_url(
+            png.into_inner(),
+            r##"["#000000", "#00ff00"]"##,
+            "EuclideanDistance",
+        )
+        .unwrap();
+
+        assert!(url.starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn transition_ends_mapped() {
+        let original = image::load_from_memory(&png()).unwrap();
+
+        let mut mapped = original.clone();
+        super::map_decoded(&mut mapped, PALETTE, "EuclideanDistance").unwrap();
+        let mapped = mapped.to_rgba8();
+
+        let frames = transition_frames(&original, PALETTE, "EuclideanDistance", 5).unwrap();
+
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0], original.to_rgba8());
+        assert_eq!(frames[4], mapped);
+
+        let gif = transition_gif(png(), PALETTE, "EuclideanDistance", 5).unwrap();
+        let frames = GifDecoder::new(Cursor::new(gif))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[4].buffer(), &mapped);
+
+        // a single frame is the mapped image
+        let single = transition_frames(&original, PALETTE, "EuclideanDistance", 0).unwrap();
+        assert_eq!(single, std::slice::from_ref(&mapped));
+
+        let many = transition_frames(&original, PALETTE, "EuclideanDistance", u32::MAX).unwrap();
+        assert_eq!(many.len(), 64);
+        assert_eq!(many[0], original.to_rgba8());
+        assert_eq!(many[63], mapped);
 
-    Ok(output.into_inner())
+        assert!(
+            transition_frames(&DynamicImage::new_rgb8(1, 1), "[]", "EuclideanDistance", 3).is_err()
+        );
+    }
 }